@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::gpu::GpuProviders;
+use crate::metrics::{self, MetricsRegistry};
+use crate::settings::SettingsState;
+use crate::sysmon::{self, SystemMonitorState, SystemSnapshot};
+use crate::validation::{self, FieldError, FieldErrorCode};
+use crate::AppRegistry;
+
+// How often the rules are evaluated
+const ALERT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub const RESOURCE_ALERT_EVENT: &str = "resource-alert";
+
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum AlertMetric {
+    CpuPercent,
+    RamPercent,
+    SwapPercent,
+    VramPercent { gpu: usize },
+    DiskFreeBytes { mount_point: String },
+    AppCpuPercent { app_id: String },
+    AppRssBytes { app_id: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Comparison {
+    Above,
+    Below,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub metric: AlertMetric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    // The condition has to hold this long before the rule fires
+    #[serde(default)]
+    pub duration_secs: u64,
+    // A firing rule only clears once the value is back past the threshold by
+    // this much, so a value hovering around it doesn't flap
+    #[serde(default)]
+    pub hysteresis: f64,
+    // Minimum time between two notifications of the same rule
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_cooldown_secs() -> u64 {
+    15 * 60
+}
+
+pub fn default_alert_rules() -> Vec<AlertRule> {
+    vec![
+        AlertRule {
+            id: "ram".to_string(),
+            name: "RAM usage".to_string(),
+            enabled: true,
+            metric: AlertMetric::RamPercent,
+            comparison: Comparison::Above,
+            threshold: 90.0,
+            duration_secs: 120,
+            hysteresis: 5.0,
+            cooldown_secs: default_cooldown_secs(),
+        },
+        AlertRule {
+            id: "vram".to_string(),
+            name: "GPU memory".to_string(),
+            enabled: true,
+            metric: AlertMetric::VramPercent { gpu: 0 },
+            comparison: Comparison::Above,
+            threshold: 95.0,
+            duration_secs: 60,
+            hysteresis: 5.0,
+            cooldown_secs: default_cooldown_secs(),
+        },
+        AlertRule {
+            id: "root-disk".to_string(),
+            name: "Free space on /".to_string(),
+            enabled: true,
+            metric: AlertMetric::DiskFreeBytes {
+                mount_point: "/".to_string(),
+            },
+            comparison: Comparison::Below,
+            threshold: 5.0 * GB,
+            duration_secs: 0,
+            hysteresis: GB,
+            cooldown_secs: 60 * 60,
+        },
+    ]
+}
+
+pub fn validate_rules(rules: &[AlertRule]) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let mut seen = Vec::new();
+    for (i, rule) in rules.iter().enumerate() {
+        let field = |name: &str| format!("rules[{}].{}", i, name);
+        if rule.id.trim().is_empty() {
+            errors.push(validation::error(field("id"), FieldErrorCode::Empty, "Alert rule id is required"));
+        } else if seen.contains(&&rule.id) {
+            errors.push(validation::error(
+                field("id"),
+                FieldErrorCode::IdCollision,
+                format!("Duplicate alert rule id '{}'", rule.id),
+            ));
+        }
+        if !rule.threshold.is_finite() {
+            errors.push(validation::error(
+                field("threshold"),
+                FieldErrorCode::InvalidValue,
+                format!("Alert rule '{}' has an invalid threshold", rule.id),
+            ));
+        }
+        if !rule.hysteresis.is_finite() || rule.hysteresis < 0.0 {
+            errors.push(validation::error(
+                field("hysteresis"),
+                FieldErrorCode::InvalidValue,
+                format!("Alert rule '{}' has an invalid hysteresis", rule.id),
+            ));
+        }
+        seen.push(&rule.id);
+    }
+    errors
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceAlertEvent {
+    pub rule_id: String,
+    pub name: String,
+    pub value: f64,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+struct RuleState {
+    breached_since: Option<Instant>,
+    firing: bool,
+    last_notified: Option<Instant>,
+}
+
+impl RuleState {
+    // Feed the latest value, returns true if a notification should go out now
+    fn update(&mut self, rule: &AlertRule, value: f64, now: Instant) -> bool {
+        let (breached, cleared) = match rule.comparison {
+            Comparison::Above => (value > rule.threshold, value < rule.threshold - rule.hysteresis),
+            Comparison::Below => (value < rule.threshold, value > rule.threshold + rule.hysteresis),
+        };
+
+        if self.firing {
+            if cleared {
+                self.firing = false;
+                self.breached_since = None;
+            }
+            return false;
+        }
+
+        if !breached {
+            self.breached_since = None;
+            return false;
+        }
+
+        let since = *self.breached_since.get_or_insert(now);
+        if now.duration_since(since) < Duration::from_secs(rule.duration_secs) {
+            return false;
+        }
+
+        // A breach inside the cooldown isn't firing yet, so it's looked at
+        // again next round and notifies once the cooldown is over
+        let cooled_down = self
+            .last_notified
+            .map(|at| now.duration_since(at) >= Duration::from_secs(rule.cooldown_secs))
+            .unwrap_or(true);
+        if cooled_down {
+            self.firing = true;
+            self.last_notified = Some(now);
+        }
+        cooled_down
+    }
+}
+
+// Readings the enabled rules need, gathered once per round
+#[derive(Default)]
+struct Readings {
+    system: Option<SystemSnapshot>,
+    vram_percent: HashMap<usize, f64>,
+    app_cpu: HashMap<String, f64>,
+    app_rss: HashMap<String, f64>,
+}
+
+impl Readings {
+    fn value(&self, metric: &AlertMetric) -> Option<f64> {
+        let system = self.system.as_ref();
+        match metric {
+            AlertMetric::CpuPercent => system.map(|s| s.cpu.total_percent),
+            AlertMetric::RamPercent => system.map(|s| s.memory.percent),
+            // No swap at all can't run out of it
+            AlertMetric::SwapPercent => system.filter(|s| s.swap.total_bytes > 0).map(|s| s.swap.percent),
+            AlertMetric::VramPercent { gpu } => self.vram_percent.get(gpu).copied(),
+            AlertMetric::DiskFreeBytes { mount_point } => system?
+                .disks
+                .iter()
+                .find(|d| d.mount_point == *mount_point)
+                .map(|d| d.available_bytes as f64),
+            AlertMetric::AppCpuPercent { app_id } => self.app_cpu.get(app_id).copied(),
+            AlertMetric::AppRssBytes { app_id } => self.app_rss.get(app_id).copied(),
+        }
+    }
+}
+
+fn read_values(app_handle: &AppHandle, rules: &[AlertRule], monitor: &mut SystemMonitorState) -> Readings {
+    let needs = |f: fn(&AlertMetric) -> bool| rules.iter().any(|r| f(&r.metric));
+    let mut readings = Readings::default();
+
+    if needs(|m| {
+        matches!(
+            m,
+            AlertMetric::CpuPercent | AlertMetric::RamPercent | AlertMetric::SwapPercent | AlertMetric::DiskFreeBytes { .. }
+        )
+    }) {
+        readings.system = Some(sysmon::snapshot(monitor));
+    }
+
+    if needs(|m| matches!(m, AlertMetric::VramPercent { .. })) {
+        for gpu in app_handle.state::<GpuProviders>().all_gpus_without_processes() {
+            if let (Some(used), Some(total)) = (gpu.vram_used_bytes, gpu.vram_total_bytes) {
+                if total > 0 {
+                    readings.vram_percent.insert(gpu.index, used as f64 / total as f64 * 100.0);
+                }
+            }
+        }
+    }
+
+    if needs(|m| matches!(m, AlertMetric::AppCpuPercent { .. } | AlertMetric::AppRssBytes { .. })) {
+        let apps: Vec<_> = match app_handle.state::<AppRegistry>().lock() {
+            Ok(apps) => apps.values().cloned().collect(),
+            Err(_) => return readings,
+        };
+        if let Ok(sampled) = metrics::sample_apps(&app_handle.state::<MetricsRegistry>(), &apps, true) {
+            for (app_id, m) in sampled {
+                readings.app_cpu.insert(app_id.clone(), m.cpu_percent);
+                readings.app_rss.insert(app_id, m.rss_bytes as f64);
+            }
+        }
+    }
+
+    readings
+}
+
+fn format_value(metric: &AlertMetric, value: f64) -> String {
+    match metric {
+        AlertMetric::DiskFreeBytes { .. } | AlertMetric::AppRssBytes { .. } => {
+            format!("{:.1} GB", value / GB)
+        }
+        _ => format!("{:.0}%", value),
+    }
+}
+
+fn describe(rule: &AlertRule, value: f64) -> String {
+    let direction = match rule.comparison {
+        Comparison::Above => "above",
+        Comparison::Below => "below",
+    };
+    let mut message = format!(
+        "{} is {} ({} {})",
+        rule.name,
+        format_value(&rule.metric, value),
+        direction,
+        format_value(&rule.metric, rule.threshold)
+    );
+    if rule.duration_secs > 0 {
+        message.push_str(&format!(" for {}s", rule.duration_secs));
+    }
+    message
+}
+
+fn notify(app_handle: &AppHandle, rule: &AlertRule, value: f64) {
+    let message = describe(rule, value);
+    eprintln!("Resource alert: {}", message);
+
+    let _ = app_handle.notification()
+        .builder()
+        .title("⚠️ Tauri Hub resource alert")
+        .body(&message)
+        .show();
+
+    let event = ResourceAlertEvent {
+        rule_id: rule.id.clone(),
+        name: rule.name.clone(),
+        value,
+        message,
+    };
+    if let Err(e) = app_handle.emit(RESOURCE_ALERT_EVENT, event) {
+        eprintln!("Failed to emit {} event: {}", RESOURCE_ALERT_EVENT, e);
+    }
+}
+
+// Background thread evaluating the alert rules from the settings every
+// ALERT_INTERVAL
+pub fn start_alert_watcher(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let mut monitor = SystemMonitorState::default();
+        let mut states: HashMap<String, (AlertRule, RuleState)> = HashMap::new();
+
+        loop {
+            let rules: Vec<AlertRule> = match app_handle.state::<SettingsState>().lock() {
+                Ok(settings) => settings.alert_rules.iter().filter(|r| r.enabled).cloned().collect(),
+                Err(_) => return,
+            };
+            // Edited or removed rules start over
+            states.retain(|id, (old, _)| rules.iter().any(|r| r.id == *id && r == old));
+
+            if !rules.is_empty() {
+                let readings = read_values(&app_handle, &rules, &mut monitor);
+                let now = Instant::now();
+                for rule in &rules {
+                    let (_, state) = states
+                        .entry(rule.id.clone())
+                        .or_insert_with(|| (rule.clone(), RuleState::default()));
+                    match readings.value(&rule.metric) {
+                        Some(value) => {
+                            if state.update(rule, value, now) {
+                                notify(&app_handle, rule, value);
+                            }
+                        }
+                        // Nothing to measure (app stopped, disk unmounted)
+                        None => *state = RuleState { last_notified: state.last_notified, ..Default::default() },
+                    }
+                }
+            }
+
+            thread::sleep(ALERT_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(comparison: Comparison, threshold: f64, duration_secs: u64) -> AlertRule {
+        AlertRule {
+            id: "r".to_string(),
+            name: "RAM usage".to_string(),
+            enabled: true,
+            metric: AlertMetric::RamPercent,
+            comparison,
+            threshold,
+            duration_secs,
+            hysteresis: 5.0,
+            cooldown_secs: 600,
+        }
+    }
+
+    #[test]
+    fn fires_after_the_condition_held_long_enough() {
+        let rule = rule(Comparison::Above, 90.0, 120);
+        let mut state = RuleState::default();
+        let t0 = Instant::now();
+
+        assert!(!state.update(&rule, 95.0, t0));
+        assert!(!state.update(&rule, 95.0, t0 + Duration::from_secs(60)));
+        // Dipping below resets the timer
+        assert!(!state.update(&rule, 80.0, t0 + Duration::from_secs(70)));
+        assert!(!state.update(&rule, 95.0, t0 + Duration::from_secs(80)));
+        assert!(!state.update(&rule, 95.0, t0 + Duration::from_secs(190)));
+        assert!(state.update(&rule, 95.0, t0 + Duration::from_secs(200)));
+        // Stays quiet while still firing
+        assert!(!state.update(&rule, 99.0, t0 + Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn hysteresis_and_cooldown_prevent_storms() {
+        let rule = rule(Comparison::Above, 90.0, 0);
+        let mut state = RuleState::default();
+        let t0 = Instant::now();
+
+        assert!(state.update(&rule, 91.0, t0));
+        // 87 is below the threshold but inside the hysteresis band
+        assert!(!state.update(&rule, 87.0, t0 + Duration::from_secs(5)));
+        assert!(!state.update(&rule, 91.0, t0 + Duration::from_secs(10)));
+        // Clearing properly and breaching again within the cooldown stays quiet
+        assert!(!state.update(&rule, 80.0, t0 + Duration::from_secs(15)));
+        assert!(!state.update(&rule, 91.0, t0 + Duration::from_secs(20)));
+        // After the cooldown a new breach notifies again
+        assert!(!state.update(&rule, 80.0, t0 + Duration::from_secs(700)));
+        assert!(state.update(&rule, 91.0, t0 + Duration::from_secs(705)));
+    }
+
+    #[test]
+    fn breach_outlasting_the_cooldown_notifies() {
+        let rule = rule(Comparison::Above, 90.0, 0);
+        let mut state = RuleState::default();
+        let t0 = Instant::now();
+
+        assert!(state.update(&rule, 91.0, t0));
+        assert!(!state.update(&rule, 80.0, t0 + Duration::from_secs(10)));
+        // Breached again inside the cooldown, and it stays that way
+        assert!(!state.update(&rule, 95.0, t0 + Duration::from_secs(20)));
+        assert!(!state.update(&rule, 95.0, t0 + Duration::from_secs(300)));
+        assert!(state.update(&rule, 95.0, t0 + Duration::from_secs(600)));
+        assert!(!state.update(&rule, 95.0, t0 + Duration::from_secs(700)));
+    }
+
+    #[test]
+    fn below_rules_and_messages() {
+        let mut rule = rule(Comparison::Below, 5.0 * GB, 0);
+        rule.name = "Free space on /".to_string();
+        rule.metric = AlertMetric::DiskFreeBytes { mount_point: "/".to_string() };
+        rule.hysteresis = GB;
+        let mut state = RuleState::default();
+        let t0 = Instant::now();
+
+        assert!(!state.update(&rule, 10.0 * GB, t0));
+        assert!(state.update(&rule, 4.0 * GB, t0));
+        assert_eq!(describe(&rule, 4.0 * GB), "Free space on / is 4.0 GB (below 5.0 GB)");
+        assert!(!state.update(&rule, 5.5 * GB, t0));
+        assert!(state.firing);
+        assert!(!state.update(&rule, 6.5 * GB, t0));
+        assert!(!state.firing);
+    }
+
+    #[test]
+    fn reports_invalid_rules_per_field() {
+        let mut rules = vec![rule(Comparison::Above, 1.0, 0), rule(Comparison::Above, 2.0, 0)];
+        rules[1].hysteresis = -1.0;
+        let fields: Vec<_> = validate_rules(&rules).into_iter().map(|e| (e.field, e.code)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("rules[1].id".to_string(), FieldErrorCode::IdCollision),
+                ("rules[1].hysteresis".to_string(), FieldErrorCode::InvalidValue),
+            ]
+        );
+        assert!(validate_rules(&default_alert_rules()).is_empty());
+    }
+}
@@ -0,0 +1,453 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::profiles::LaunchProfile;
+use crate::{expand_home, AppStatus, TauriApp};
+
+// How deep below a root we look for projects, apps/<name> is depth 1
+const MAX_DEPTH: usize = 4;
+
+// Directories that never contain projects of their own but can be huge
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "dist", "build"];
+
+// A Tauri project found on disk, proposed for registration
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredApp {
+    pub app: TauriApp,
+    pub identifier: Option<String>,
+    // Bundle icons from tauri.conf.json, resolved against src-tauri/
+    pub icons: Vec<PathBuf>,
+    // Id of the app already registered with this path, if any
+    pub registered_as: Option<String>,
+}
+
+pub fn is_tauri_project(dir: &Path) -> bool {
+    dir.join("src-tauri").join("tauri.conf.json").is_file() && dir.join("package.json").is_file()
+}
+
+fn find_projects(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    if is_tauri_project(dir) {
+        found.push(dir.to_path_buf());
+        return;
+    }
+    if depth >= MAX_DEPTH {
+        return;
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read {:?} during discovery: {}", dir, e);
+            return;
+        }
+    };
+
+    let mut subdirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .map(|entry| entry.path())
+        .collect();
+    subdirs.sort();
+
+    for subdir in subdirs {
+        find_projects(&subdir, depth + 1, found);
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+}
+
+// Tauri v2 keeps these at the top level, v1 nests them under package/tauri
+fn conf_str(conf: &Value, v2: &str, v1: &[&str]) -> Option<String> {
+    conf.get(v2)
+        .or_else(|| v1.iter().try_fold(conf, |value, key| value.get(key)))
+        .and_then(Value::as_str)
+        .map(|s| s.to_string())
+}
+
+fn package_manager(project: &Path, package: &Value) -> &'static str {
+    // An explicit "packageManager": "pnpm@9.0.0" wins over lockfiles
+    if let Some(declared) = package.get("packageManager").and_then(Value::as_str) {
+        for pm in ["pnpm", "yarn", "bun", "npm"] {
+            if declared.starts_with(pm) {
+                return pm;
+            }
+        }
+    }
+
+    // Workspaces keep the lockfile at the top, so check the parents too
+    for dir in project.ancestors() {
+        if dir.join("pnpm-lock.yaml").is_file() {
+            return "pnpm";
+        }
+        if dir.join("yarn.lock").is_file() {
+            return "yarn";
+        }
+        if dir.join("bun.lockb").is_file() || dir.join("bun.lock").is_file() {
+            return "bun";
+        }
+        if dir.join("package-lock.json").is_file() {
+            return "npm";
+        }
+    }
+    "npm"
+}
+
+fn profile(name: &str, executable: &str, args: &[&str]) -> LaunchProfile {
+    LaunchProfile {
+        name: name.to_string(),
+        executable: executable.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        env: HashMap::new(),
+        working_dir: None,
+    }
+}
+
+fn dev_profile(project: &Path, package: &Value) -> LaunchProfile {
+    let scripts = package.get("scripts");
+    let has_script = |name: &str| scripts.and_then(|s| s.get(name)).is_some();
+    let pm = package_manager(project, package);
+
+    if has_script("tauri:dev") {
+        profile("dev", pm, &["run", "tauri:dev"])
+    } else if has_script("tauri") {
+        // npm needs "--" to pass arguments through to the script
+        if pm == "npm" {
+            profile("dev", pm, &["run", "tauri", "--", "dev"])
+        } else {
+            profile("dev", pm, &["run", "tauri", "dev"])
+        }
+    } else {
+        profile("dev", "cargo", &["tauri", "dev"])
+    }
+}
+
+// Name of the [package] in src-tauri/Cargo.toml, which is what the built binary
+// is called unless tauri.conf.json overrides it
+fn cargo_package_name(src_tauri: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(src_tauri.join("Cargo.toml")).ok()?;
+    let mut in_package = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "name" {
+                return Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    None
+}
+
+fn release_profile(src_tauri: &Path, conf: &Value) -> Option<LaunchProfile> {
+    let binary = conf_str(conf, "mainBinaryName", &[]).or_else(|| cargo_package_name(src_tauri))?;
+    let relative = Path::new("src-tauri")
+        .join("target")
+        .join("release")
+        .join(&binary);
+    if !src_tauri
+        .join("target")
+        .join("release")
+        .join(&binary)
+        .is_file()
+    {
+        return None;
+    }
+    Some(profile("release", &relative.to_string_lossy(), &[]))
+}
+
+fn slugify(name: &str) -> String {
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "app".to_string()
+    } else {
+        slug
+    }
+}
+
+fn unique_id(base: String, taken: &mut HashSet<String>) -> String {
+    let mut id = base.clone();
+    let mut n = 2;
+    while taken.contains(&id) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    taken.insert(id.clone());
+    id
+}
+
+// Name and version as declared by the project's tauri.conf.json
+pub struct ProjectInfo {
+    pub name: Option<String>,
+    pub identifier: Option<String>,
+    pub version: Option<String>,
+}
+
+fn read_project_info(project: &Path, conf: &Value, package: &Value) -> ProjectInfo {
+    let src_tauri = project.join("src-tauri");
+
+    // v2 may point "version" at a package.json instead of spelling it out
+    let version = match conf_str(conf, "version", &["package", "version"]) {
+        Some(v) if v.ends_with(".json") => read_json(&src_tauri.join(v)).ok().and_then(|p| {
+            p.get("version")
+                .and_then(Value::as_str)
+                .map(|s| s.to_string())
+        }),
+        Some(v) => Some(v),
+        None => package
+            .get("version")
+            .and_then(Value::as_str)
+            .map(|s| s.to_string()),
+    };
+
+    ProjectInfo {
+        name: conf_str(conf, "productName", &["package", "productName"]),
+        identifier: conf_str(conf, "identifier", &["tauri", "bundle", "identifier"]),
+        version,
+    }
+}
+
+pub fn project_info(project: &Path) -> Result<ProjectInfo, String> {
+    let conf = read_json(&project.join("src-tauri").join("tauri.conf.json"))?;
+    let package = read_json(&project.join("package.json")).unwrap_or(Value::Null);
+    Ok(read_project_info(project, &conf, &package))
+}
+
+fn inspect_project(
+    project: &Path,
+    taken_ids: &mut HashSet<String>,
+) -> Result<DiscoveredApp, String> {
+    let src_tauri = project.join("src-tauri");
+    let conf = read_json(&src_tauri.join("tauri.conf.json"))?;
+    let package = read_json(&project.join("package.json"))?;
+    let info = read_project_info(project, &conf, &package);
+
+    let dir_name = project
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let icons = conf
+        .get("bundle")
+        .or_else(|| conf.get("tauri").and_then(|t| t.get("bundle")))
+        .and_then(|b| b.get("icon"))
+        .and_then(Value::as_array)
+        .map(|icons| {
+            icons
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|icon| src_tauri.join(icon))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let dev = dev_profile(project, &package);
+    let mut profiles = vec![dev.clone()];
+    profiles.extend(release_profile(&src_tauri, &conf));
+
+    let description = package
+        .get("description")
+        .and_then(Value::as_str)
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("Discovered in {}", project.display()));
+
+    let app = TauriApp {
+        id: unique_id(slugify(&dir_name), taken_ids),
+        name: info.name.unwrap_or_else(|| dir_name.clone()),
+        description,
+        path: project.to_path_buf(),
+        executable: dev.executable.clone(),
+        icon: None,
+        status: AppStatus::Stopped,
+        stop_grace_period_secs: crate::default_stop_grace_period_secs(),
+        last_exit: None,
+        pid: None,
+        start_time: None,
+        profiles,
+        default_profile: Some(dev.name),
+        active_profile: None,
+        restart_policy: Default::default(),
+        status_reason: None,
+        version: info.version,
+        missing: false,
+    };
+
+    Ok(DiscoveredApp {
+        app,
+        identifier: info.identifier,
+        icons,
+        registered_as: None,
+    })
+}
+
+// Walk the roots for Tauri projects and describe each one as a ready-to-register
+// app. Projects that are already registered are reported with registered_as set
+// instead of being left out, so the caller can show them as such.
+pub fn discover(roots: &[PathBuf], registered: &HashMap<String, TauriApp>) -> Vec<DiscoveredApp> {
+    let mut projects = Vec::new();
+    for root in roots {
+        let root = expand_home(root);
+        if !root.is_dir() {
+            eprintln!("Skipping discovery root {:?}: not a directory", root);
+            continue;
+        }
+        find_projects(&root, 0, &mut projects);
+    }
+
+    let canonical = |path: &Path| fs::canonicalize(expand_home(path)).ok();
+    let registered_paths: HashMap<PathBuf, String> = registered
+        .values()
+        .filter_map(|app| canonical(&app.path).map(|path| (path, app.id.clone())))
+        .collect();
+
+    let mut taken_ids: HashSet<String> = registered.keys().cloned().collect();
+    let mut seen = HashSet::new();
+    let mut discovered = Vec::new();
+
+    for project in projects {
+        let project = canonical(&project).unwrap_or(project);
+        // Overlapping roots would otherwise report a project twice
+        if !seen.insert(project.clone()) {
+            continue;
+        }
+
+        match inspect_project(&project, &mut taken_ids) {
+            Ok(mut found) => {
+                found.registered_as = registered_paths.get(&project).cloned();
+                discovered.push(found);
+            }
+            Err(e) => eprintln!("Skipping discovered project {:?}: {}", project, e),
+        }
+    }
+    discovered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    fn project(fx: &Fixture, dir: &str, conf: &str, package: &str) {
+        fx.write(&format!("{}/src-tauri/tauri.conf.json", dir), conf);
+        fx.write(&format!("{}/package.json", dir), package);
+    }
+
+    #[test]
+    fn reads_v2_config_and_infers_pnpm_profile() {
+        let fx = Fixture::new("v2");
+        fx.write("pnpm-lock.yaml", "");
+        project(
+            &fx,
+            "apps/pdf-search",
+            r#"{
+                "productName": "PDF Search",
+                "version": "0.1.0",
+                "identifier": "com.example.pdf",
+                "bundle": { "icon": ["icons/32x32.png", "icons/icon.ico"] }
+            }"#,
+            r#"{ "scripts": { "tauri": "tauri" } }"#,
+        );
+        // Nothing inside node_modules counts as a project
+        project(&fx, "apps/node_modules/dep", "{}", "{}");
+
+        let found = discover(&[fx.dir.join("apps")], &HashMap::new());
+        assert_eq!(found.len(), 1);
+
+        let found = &found[0];
+        assert_eq!(found.app.id, "pdf-search");
+        assert_eq!(found.app.name, "PDF Search");
+        assert_eq!(found.identifier.as_deref(), Some("com.example.pdf"));
+        assert_eq!(found.app.version.as_deref(), Some("0.1.0"));
+        assert_eq!(
+            found.icons,
+            vec![
+                fx.dir.join("apps/pdf-search/src-tauri/icons/32x32.png"),
+                fx.dir.join("apps/pdf-search/src-tauri/icons/icon.ico"),
+            ]
+        );
+        assert_eq!(found.app.executable, "pnpm");
+        assert_eq!(found.app.profiles[0].args, vec!["run", "tauri", "dev"]);
+        assert_eq!(found.registered_as, None);
+    }
+
+    #[test]
+    fn reads_v1_config_and_falls_back_to_cargo() {
+        let fx = Fixture::new("v1");
+        project(
+            &fx,
+            "legacy",
+            r#"{
+                "package": { "productName": "Legacy", "version": "2.0.0" },
+                "tauri": { "bundle": { "identifier": "com.example.legacy" } }
+            }"#,
+            r#"{ "scripts": { "dev": "vite" } }"#,
+        );
+        fx.write(
+            "legacy/src-tauri/Cargo.toml",
+            "[package]\nname = \"legacy-app\"\n",
+        );
+        fx.write("legacy/src-tauri/target/release/legacy-app", "");
+
+        let found = discover(std::slice::from_ref(&fx.dir), &HashMap::new());
+        assert_eq!(found.len(), 1);
+
+        let found = &found[0];
+        assert_eq!(found.app.name, "Legacy");
+        assert_eq!(found.identifier.as_deref(), Some("com.example.legacy"));
+        assert_eq!(found.app.version.as_deref(), Some("2.0.0"));
+        assert_eq!(found.app.executable, "cargo");
+        assert_eq!(found.app.profiles[0].args, vec!["tauri", "dev"]);
+        assert_eq!(found.app.profiles[1].name, "release");
+        assert_eq!(
+            found.app.profiles[1].executable,
+            "src-tauri/target/release/legacy-app"
+        );
+    }
+
+    #[test]
+    fn marks_registered_projects_and_avoids_id_collisions() {
+        let fx = Fixture::new("registered");
+        project(&fx, "one/app", "{}", "{}");
+        project(&fx, "two/app", "{}", "{}");
+
+        let mut registered = HashMap::new();
+        let mut existing = discover(&[fx.dir.join("one")], &HashMap::new())
+            .remove(0)
+            .app;
+        existing.id = "app".to_string();
+        registered.insert(existing.id.clone(), existing);
+
+        let found = discover(&[fx.dir.clone(), fx.dir.join("one")], &registered);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].registered_as.as_deref(), Some("app"));
+        assert_eq!(found[1].registered_as, None);
+        assert_ne!(found[0].app.id, "app");
+        assert_ne!(found[1].app.id, "app");
+        assert_ne!(found[0].app.id, found[1].app.id);
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+use std::io;
+use std::process::{Command, Output};
+
+use serde::Serialize;
+
+use crate::validation::FieldError;
+
+// Exit status pkexec uses when the user dismisses the authentication dialog
+const PKEXEC_DISMISSED: i32 = 126;
+// ...and when authorization could not be obtained at all
+const PKEXEC_NOT_AUTHORIZED: i32 = 127;
+
+// D-Bus error names worth telling apart from other failures
+const NO_SUCH_UNIT: &str = "org.freedesktop.systemd1.NoSuchUnit";
+const ACCESS_DENIED: &str = "org.freedesktop.DBus.Error.AccessDenied";
+const AUTH_REQUIRED: &str = "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired";
+
+// Error returned by every command. Serialized as an object whose `code` is
+// one of the snake_case variant names below, so the frontend can branch on
+// it instead of matching message text:
+//   { "code": "auth_cancelled", "message": "..." }
+//   { "code": "command_failed", "message": "...", "command": "systemctl", "exit_code": 1, "stderr": "..." }
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum HubError {
+    NotFound {
+        message: String,
+    },
+    // pkexec prompt dismissed or authorization refused
+    AuthCancelled {
+        message: String,
+    },
+    // The binary a command shells out to isn't installed
+    CommandMissing {
+        message: String,
+        command: String,
+    },
+    CommandFailed {
+        message: String,
+        command: String,
+        exit_code: Option<i32>,
+        stderr: String,
+    },
+    Io {
+        message: String,
+    },
+    Parse {
+        message: String,
+    },
+    // The submitted app failed validation; `fields` says where
+    Validation {
+        message: String,
+        fields: Vec<FieldError>,
+    },
+    // The request conflicts with the current state, e.g. starting a
+    // recording while one is running
+    InvalidState {
+        message: String,
+    },
+    // The user stopped a long-running job, e.g. a transcription
+    Cancelled {
+        message: String,
+    },
+    // Anything else: lock poisoning, failed background tasks, internal helpers
+    Internal {
+        message: String,
+    },
+}
+
+impl HubError {
+    pub fn not_found(message: impl Into<String>) -> Self {
+        HubError::NotFound { message: message.into() }
+    }
+
+    pub fn invalid_state(message: impl Into<String>) -> Self {
+        HubError::InvalidState { message: message.into() }
+    }
+
+    pub fn io(context: &str, e: io::Error) -> Self {
+        HubError::Io { message: format!("{}: {}", context, e) }
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        HubError::Cancelled { message: message.into() }
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        HubError::Parse { message: message.into() }
+    }
+
+    pub fn validation(fields: Vec<FieldError>) -> Self {
+        let message = fields
+            .iter()
+            .map(|f| f.message.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+        HubError::Validation { message, fields }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            HubError::NotFound { message }
+            | HubError::AuthCancelled { message }
+            | HubError::CommandMissing { message, .. }
+            | HubError::CommandFailed { message, .. }
+            | HubError::Io { message }
+            | HubError::Parse { message }
+            | HubError::Validation { message, .. }
+            | HubError::InvalidState { message }
+            | HubError::Cancelled { message }
+            | HubError::Internal { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for HubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for HubError {}
+
+// For errors no caller is expected to handle; anything the frontend may want
+// to tell apart (I/O, parsing, ...) should be built as its own variant
+impl From<String> for HubError {
+    fn from(message: String) -> Self {
+        HubError::Internal { message }
+    }
+}
+
+impl From<&str> for HubError {
+    fn from(message: &str) -> Self {
+        HubError::Internal { message: message.to_string() }
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for HubError {
+    fn from(e: std::sync::PoisonError<T>) -> Self {
+        HubError::Internal { message: e.to_string() }
+    }
+}
+
+// A missing unit and a refused polkit check keep their own variants so the
+// frontend can tell them apart from a broken bus
+impl From<zbus::Error> for HubError {
+    fn from(e: zbus::Error) -> Self {
+        let name = match &e {
+            zbus::Error::MethodError(name, ..) => Some(name.as_str()),
+            zbus::Error::FDO(fdo) => match **fdo {
+                zbus::fdo::Error::AccessDenied(_) => Some(ACCESS_DENIED),
+                zbus::fdo::Error::InteractiveAuthorizationRequired(_) => Some(AUTH_REQUIRED),
+                _ => None,
+            },
+            _ => None,
+        };
+        let message = format!("D-Bus error: {}", e);
+        match name {
+            Some(NO_SUCH_UNIT) => HubError::NotFound { message },
+            Some(ACCESS_DENIED | AUTH_REQUIRED) => HubError::AuthCancelled { message },
+            _ => HubError::Internal { message },
+        }
+    }
+}
+
+fn program_name(cmd: &Command) -> String {
+    cmd.get_program().to_string_lossy().into_owned()
+}
+
+// Error for a command that couldn't be started at all
+pub fn spawn_error(program: &str, what: &str, e: io::Error) -> HubError {
+    if e.kind() == io::ErrorKind::NotFound {
+        HubError::CommandMissing {
+            message: format!("Failed to {}: '{}' is not installed", what, program),
+            command: program.to_string(),
+        }
+    } else {
+        HubError::io(&format!("Failed to {}", what), e)
+    }
+}
+
+// Run `cmd` to completion. A missing binary becomes CommandMissing; the exit
+// status is left for the caller to judge.
+pub fn output(cmd: &mut Command, what: &str) -> Result<Output, HubError> {
+    cmd.output().map_err(|e| spawn_error(&program_name(cmd), what, e))
+}
+
+// pkexec's own failures: a dismissed or refused prompt, or a program it
+// couldn't find. None when the exit status came from the wrapped command.
+fn pkexec_error(cmd: &Command, out: &Output, what: &str) -> Option<HubError> {
+    if program_name(cmd) != "pkexec" {
+        return None;
+    }
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    match out.status.code() {
+        Some(PKEXEC_DISMISSED) => Some(HubError::AuthCancelled {
+            message: format!("Failed to {}: authentication cancelled", what),
+        }),
+        Some(PKEXEC_NOT_AUTHORIZED) if stderr.contains("Cannot run program") => Some(HubError::CommandMissing {
+            message: format!("Failed to {}: {}", what, stderr.trim()),
+            command: cmd
+                .get_args()
+                .next()
+                .map(|a| a.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }),
+        Some(PKEXEC_NOT_AUTHORIZED) => Some(HubError::AuthCancelled {
+            message: format!("Failed to {}: not authorized", what),
+        }),
+        _ => None,
+    }
+}
+
+// For commands whose exit status means something other than failure (AIDE
+// exits non-zero when it finds changes): only pkexec's own failures count
+pub fn check_auth(cmd: &Command, out: Output, what: &str) -> Result<Output, HubError> {
+    match pkexec_error(cmd, &out, what) {
+        Some(e) => Err(e),
+        None => Ok(out),
+    }
+}
+
+// Turn an unsuccessful exit into an error
+pub fn check(cmd: &Command, out: Output, what: &str) -> Result<Output, HubError> {
+    if out.status.success() {
+        return Ok(out);
+    }
+    if let Some(e) = pkexec_error(cmd, &out, what) {
+        return Err(e);
+    }
+
+    let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+    Err(HubError::CommandFailed {
+        message: if stderr.is_empty() {
+            format!("Failed to {}", what)
+        } else {
+            format!("Failed to {}: {}", what, stderr)
+        },
+        command: program_name(cmd),
+        exit_code: out.status.code(),
+        stderr,
+    })
+}
+
+// `output` followed by `check`
+pub fn run(cmd: &mut Command, what: &str) -> Result<Output, HubError> {
+    let out = output(cmd, what)?;
+    check(cmd, out, what)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_with_a_stable_code() {
+        let err = HubError::CommandFailed {
+            message: "Failed to start docker: boom".to_string(),
+            command: "systemctl".to_string(),
+            exit_code: Some(1),
+            stderr: "boom".to_string(),
+        };
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["code"], "command_failed");
+        assert_eq!(json["exit_code"], 1);
+        assert_eq!(json["stderr"], "boom");
+
+        let json = serde_json::to_value(HubError::from("poisoned".to_string())).unwrap();
+        assert_eq!(json, serde_json::json!({ "code": "internal", "message": "poisoned" }));
+    }
+
+    #[test]
+    fn classifies_command_results() {
+        let err = run(&mut Command::new("/nonexistent/hub-test-binary"), "run it").unwrap_err();
+        assert!(matches!(err, HubError::CommandMissing { ref command, .. } if command == "/nonexistent/hub-test-binary"));
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo nope >&2; exit 3"]);
+        match run(&mut cmd, "run sh").unwrap_err() {
+            HubError::CommandFailed { exit_code, stderr, .. } => {
+                assert_eq!(exit_code, Some(3));
+                assert_eq!(stderr, "nope");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        assert!(run(Command::new("true").arg("x"), "run true").is_ok());
+    }
+
+    #[test]
+    fn classifies_dbus_errors() {
+        let call = zbus::Message::method_call("/org/freedesktop/systemd1", "GetUnit")
+            .unwrap()
+            .build(&())
+            .unwrap();
+        let reply = zbus::Message::error(&call.header(), NO_SUCH_UNIT)
+            .unwrap()
+            .build(&("Unit foo.service not loaded.",))
+            .unwrap();
+        assert!(matches!(HubError::from(zbus::Error::from(reply)), HubError::NotFound { .. }));
+
+        let denied = zbus::fdo::Error::InteractiveAuthorizationRequired("polkit".to_string());
+        assert!(matches!(
+            HubError::from(zbus::Error::FDO(Box::new(denied))),
+            HubError::AuthCancelled { .. }
+        ));
+        assert!(matches!(HubError::from(zbus::Error::InvalidReply), HubError::Internal { .. }));
+    }
+}
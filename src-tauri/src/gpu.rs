@@ -0,0 +1,592 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+// Set to a JSON file (see FakeProvider) to replace real GPU detection, for
+// development and tests on machines without a GPU
+pub const GPU_FIXTURE_ENV: &str = "TAURI_HUB_GPU_FIXTURE";
+
+const PCI_VENDOR_AMD: &str = "0x1002";
+const PCI_VENDOR_INTEL: &str = "0x8086";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GpuVendor {
+    Nvidia,
+    Amd,
+    Intel,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    pub vram_bytes: u64,
+}
+
+// Anything a backend can't read is None rather than a made-up zero
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GpuInfo {
+    // Position in the combined list of all providers
+    #[serde(default)]
+    pub index: usize,
+    pub vendor: GpuVendor,
+    pub name: String,
+    #[serde(default)]
+    pub utilization_percent: Option<f64>,
+    #[serde(default)]
+    pub vram_used_bytes: Option<u64>,
+    #[serde(default)]
+    pub vram_total_bytes: Option<u64>,
+    #[serde(default)]
+    pub temperature_celsius: Option<f64>,
+    #[serde(default)]
+    pub processes: Vec<GpuProcess>,
+}
+
+pub trait GpuProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+    // Per-process VRAM is the expensive part (a second nvidia-smi run, a scan
+    // of every /proc/<pid>/fdinfo), so it is only collected when asked for
+    fn gpus(&self, with_processes: bool) -> Result<Vec<GpuInfo>, String>;
+}
+
+// Providers found on this machine, in the order their GPUs are listed
+pub struct GpuProviders(pub Vec<Box<dyn GpuProvider>>);
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn read_number<T: std::str::FromStr>(path: impl AsRef<Path>) -> Option<T> {
+    read_trimmed(path)?.parse().ok()
+}
+
+fn process_name(proc_root: &Path, pid: u32) -> String {
+    read_trimmed(proc_root.join(pid.to_string()).join("comm")).unwrap_or_default()
+}
+
+// nvidia-smi
+
+pub struct NvidiaSmiProvider;
+
+// "[N/A]" and friends show up for fields a card doesn't support
+fn nvidia_value<T: std::str::FromStr>(field: &str) -> Option<T> {
+    field.trim().parse().ok()
+}
+
+const MIB: u64 = 1024 * 1024;
+
+// Output of --query-gpu=index,uuid,name,utilization.gpu,memory.used,memory.total,temperature.gpu
+fn parse_nvidia_gpus(csv: &str) -> Vec<(String, GpuInfo)> {
+    csv.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            Some((
+                fields[1].to_string(),
+                GpuInfo {
+                    index: nvidia_value(fields[0]).unwrap_or(0),
+                    vendor: GpuVendor::Nvidia,
+                    name: fields[2].to_string(),
+                    utilization_percent: nvidia_value(fields[3]),
+                    vram_used_bytes: nvidia_value::<u64>(fields[4]).map(|mib| mib * MIB),
+                    vram_total_bytes: nvidia_value::<u64>(fields[5]).map(|mib| mib * MIB),
+                    temperature_celsius: nvidia_value(fields[6]),
+                    processes: Vec::new(),
+                },
+            ))
+        })
+        .collect()
+}
+
+// Output of --query-compute-apps=gpu_uuid,pid,process_name,used_memory
+fn parse_nvidia_processes(csv: &str) -> Vec<(String, GpuProcess)> {
+    csv.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() < 4 {
+                return None;
+            }
+            Some((
+                fields[0].to_string(),
+                GpuProcess {
+                    pid: nvidia_value(fields[1])?,
+                    name: fields[2].to_string(),
+                    vram_bytes: nvidia_value::<u64>(fields[3]).unwrap_or(0) * MIB,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn run_nvidia_smi(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("nvidia-smi")
+        .args(args)
+        .arg("--format=csv,noheader,nounits")
+        .output()
+        .map_err(|e| format!("Failed to run nvidia-smi: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "nvidia-smi failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+impl GpuProvider for NvidiaSmiProvider {
+    fn name(&self) -> &'static str {
+        "nvidia-smi"
+    }
+
+    fn gpus(&self, with_processes: bool) -> Result<Vec<GpuInfo>, String> {
+        let gpus = run_nvidia_smi(&[
+            "--query-gpu=index,uuid,name,utilization.gpu,memory.used,memory.total,temperature.gpu",
+        ])?;
+        // Per-process numbers are a bonus, don't lose the GPUs over them
+        let processes = if with_processes {
+            run_nvidia_smi(&["--query-compute-apps=gpu_uuid,pid,process_name,used_memory"])
+                .map(|csv| parse_nvidia_processes(&csv))
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        Ok(parse_nvidia_gpus(&gpus)
+            .into_iter()
+            .map(|(uuid, mut gpu)| {
+                gpu.processes = processes
+                    .iter()
+                    .filter(|(gpu_uuid, _)| *gpu_uuid == uuid)
+                    .map(|(_, p)| p.clone())
+                    .collect();
+                gpu
+            })
+            .collect())
+    }
+}
+
+// DRM sysfs, shared by the AMD and Intel providers
+
+// A /sys/class/drm/cardN directory whose PCI vendor matched
+struct DrmCard {
+    card: String,
+    device: PathBuf,
+    // PCI address like 0000:03:00.0, used to match fdinfo entries
+    pdev: Option<String>,
+}
+
+fn drm_cards(sys_root: &Path, vendor: &str) -> Vec<DrmCard> {
+    let drm = sys_root.join("class/drm");
+    let mut cards: Vec<DrmCard> = match fs::read_dir(&drm) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            // card0, not the card0-DP-1 connectors
+            .filter(|name| {
+                name.len() > 4 && name.starts_with("card") && name[4..].chars().all(|c| c.is_ascii_digit())
+            })
+            .filter_map(|card| {
+                let device = drm.join(&card).join("device");
+                if read_trimmed(device.join("vendor")).as_deref() != Some(vendor) {
+                    return None;
+                }
+                let pdev = read_trimmed(device.join("uevent")).and_then(|uevent| {
+                    uevent
+                        .lines()
+                        .find_map(|l| l.strip_prefix("PCI_SLOT_NAME=").map(|s| s.to_string()))
+                });
+                Some(DrmCard { card, device, pdev })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    cards.sort_by(|a, b| a.card.cmp(&b.card));
+    cards
+}
+
+// First temperature reported by the card's hwmon, in °C
+fn drm_temperature(device: &Path) -> Option<f64> {
+    let mut hwmons: Vec<PathBuf> = fs::read_dir(device.join("hwmon"))
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    hwmons.sort();
+    hwmons
+        .iter()
+        .find_map(|hwmon| read_number::<f64>(hwmon.join("temp1_input")))
+        .map(|millidegrees| millidegrees / 1000.0)
+}
+
+fn fdinfo_kib(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace();
+    let amount: u64 = parts.next()?.parse().ok()?;
+    Some(match parts.next() {
+        Some("KiB") => amount * 1024,
+        Some("MiB") => amount * 1024 * 1024,
+        Some("GiB") => amount * 1024 * 1024 * 1024,
+        _ => amount,
+    })
+}
+
+// Device memory per process from /proc/<pid>/fdinfo, which amdgpu, i915 and
+// xe fill in with drm-* keys. A process can have the same DRM client open on
+// several fds, so clients are counted once.
+fn drm_fdinfo_processes(proc_root: &Path, pdev: &str, memory_keys: &[&str]) -> Vec<GpuProcess> {
+    let mut per_pid: HashMap<u32, u64> = HashMap::new();
+
+    let pids = match fs::read_dir(proc_root) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|e| e.file_name().to_string_lossy().parse::<u32>().ok()),
+        Err(_) => return Vec::new(),
+    };
+
+    for pid in pids {
+        let fdinfo_dir = proc_root.join(pid.to_string()).join("fdinfo");
+        let entries = match fs::read_dir(&fdinfo_dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        let mut clients = HashMap::new();
+        for entry in entries.flatten() {
+            let content = match fs::read_to_string(entry.path()) {
+                Ok(content) if content.contains("drm-client-id") => content,
+                _ => continue,
+            };
+
+            let fields: HashMap<&str, &str> = content
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .collect();
+            if fields.get("drm-pdev") != Some(&pdev) {
+                continue;
+            }
+            let client = fields.get("drm-client-id").copied().unwrap_or_default();
+            let bytes: u64 = memory_keys
+                .iter()
+                .filter_map(|key| fields.get(key).and_then(|v| fdinfo_kib(v)))
+                .sum();
+            clients.insert(client.to_string(), bytes);
+        }
+
+        let total: u64 = clients.values().sum();
+        if total > 0 {
+            *per_pid.entry(pid).or_default() += total;
+        }
+    }
+
+    let mut processes: Vec<GpuProcess> = per_pid
+        .into_iter()
+        .map(|(pid, vram_bytes)| GpuProcess {
+            pid,
+            name: process_name(proc_root, pid),
+            vram_bytes,
+        })
+        .collect();
+    processes.sort_by(|a, b| b.vram_bytes.cmp(&a.vram_bytes).then(a.pid.cmp(&b.pid)));
+    processes
+}
+
+pub struct AmdSysfsProvider {
+    sys_root: PathBuf,
+    proc_root: PathBuf,
+}
+
+impl AmdSysfsProvider {
+    pub fn new(sys_root: impl Into<PathBuf>, proc_root: impl Into<PathBuf>) -> Self {
+        AmdSysfsProvider {
+            sys_root: sys_root.into(),
+            proc_root: proc_root.into(),
+        }
+    }
+}
+
+impl GpuProvider for AmdSysfsProvider {
+    fn name(&self) -> &'static str {
+        "amdgpu sysfs"
+    }
+
+    fn gpus(&self, with_processes: bool) -> Result<Vec<GpuInfo>, String> {
+        Ok(drm_cards(&self.sys_root, PCI_VENDOR_AMD)
+            .into_iter()
+            .map(|card| GpuInfo {
+                index: 0,
+                vendor: GpuVendor::Amd,
+                name: read_trimmed(card.device.join("product_name"))
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or_else(|| format!("AMD GPU ({})", card.card)),
+                utilization_percent: read_number(card.device.join("gpu_busy_percent")),
+                vram_used_bytes: read_number(card.device.join("mem_info_vram_used")),
+                vram_total_bytes: read_number(card.device.join("mem_info_vram_total")),
+                temperature_celsius: drm_temperature(&card.device),
+                processes: card
+                    .pdev
+                    .as_deref()
+                    .filter(|_| with_processes)
+                    .map(|pdev| drm_fdinfo_processes(&self.proc_root, pdev, &["drm-memory-vram"]))
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+}
+
+// i915 has no busy percentage in sysfs, so utilization stays None. Discrete
+// cards on the xe driver report VRAM through the same fdinfo keys.
+pub struct IntelSysfsProvider {
+    sys_root: PathBuf,
+    proc_root: PathBuf,
+}
+
+impl IntelSysfsProvider {
+    pub fn new(sys_root: impl Into<PathBuf>, proc_root: impl Into<PathBuf>) -> Self {
+        IntelSysfsProvider {
+            sys_root: sys_root.into(),
+            proc_root: proc_root.into(),
+        }
+    }
+}
+
+impl GpuProvider for IntelSysfsProvider {
+    fn name(&self) -> &'static str {
+        "i915/xe sysfs"
+    }
+
+    fn gpus(&self, with_processes: bool) -> Result<Vec<GpuInfo>, String> {
+        Ok(drm_cards(&self.sys_root, PCI_VENDOR_INTEL)
+            .into_iter()
+            .map(|card| {
+                let processes = card
+                    .pdev
+                    .as_deref()
+                    .filter(|_| with_processes)
+                    .map(|pdev| {
+                        drm_fdinfo_processes(
+                            &self.proc_root,
+                            pdev,
+                            &["drm-total-vram0", "drm-total-local0"],
+                        )
+                    })
+                    .unwrap_or_default();
+                GpuInfo {
+                    index: 0,
+                    vendor: GpuVendor::Intel,
+                    name: format!("Intel GPU ({})", card.card),
+                    utilization_percent: None,
+                    vram_used_bytes: read_number(card.device.join("mem_info_vram_used")),
+                    vram_total_bytes: read_number(card.device.join("mem_info_vram_total")),
+                    temperature_celsius: drm_temperature(&card.device),
+                    processes,
+                }
+            })
+            .collect())
+    }
+}
+
+// Reads the GPU list from a JSON file holding an array of GpuInfo. The file is
+// re-read on every call so a test can change it between readings.
+pub struct FakeProvider {
+    fixture: PathBuf,
+}
+
+impl FakeProvider {
+    pub fn new(fixture: impl Into<PathBuf>) -> Self {
+        FakeProvider {
+            fixture: fixture.into(),
+        }
+    }
+}
+
+impl GpuProvider for FakeProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn gpus(&self, _with_processes: bool) -> Result<Vec<GpuInfo>, String> {
+        let content = fs::read_to_string(&self.fixture)
+            .map_err(|e| format!("Failed to read GPU fixture {:?}: {}", self.fixture, e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse GPU fixture {:?}: {}", self.fixture, e))
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+pub fn detect_providers() -> GpuProviders {
+    if let Some(fixture) = std::env::var_os(GPU_FIXTURE_ENV) {
+        return GpuProviders(vec![Box::new(FakeProvider::new(fixture))]);
+    }
+
+    let mut providers: Vec<Box<dyn GpuProvider>> = Vec::new();
+    if on_path("nvidia-smi") {
+        providers.push(Box::new(NvidiaSmiProvider));
+    }
+    let sys_root = Path::new("/sys");
+    if !drm_cards(sys_root, PCI_VENDOR_AMD).is_empty() {
+        providers.push(Box::new(AmdSysfsProvider::new("/sys", "/proc")));
+    }
+    if !drm_cards(sys_root, PCI_VENDOR_INTEL).is_empty() {
+        providers.push(Box::new(IntelSysfsProvider::new("/sys", "/proc")));
+    }
+    providers
+        .iter()
+        .for_each(|p| eprintln!("GPU provider: {}", p.name()));
+    GpuProviders(providers)
+}
+
+impl GpuProviders {
+    // GPUs of every provider, numbered in order. A failing provider is logged
+    // and skipped so one broken driver doesn't hide the other cards.
+    fn collect(&self, with_processes: bool) -> Vec<GpuInfo> {
+        let mut gpus = Vec::new();
+        for provider in &self.0 {
+            match provider.gpus(with_processes) {
+                Ok(found) => gpus.extend(found),
+                Err(e) => eprintln!("GPU provider {} failed: {}", provider.name(), e),
+            }
+        }
+        for (index, gpu) in gpus.iter_mut().enumerate() {
+            gpu.index = index;
+        }
+        gpus
+    }
+
+    pub fn all_gpus(&self) -> Vec<GpuInfo> {
+        self.collect(true)
+    }
+
+    // Utilization, VRAM and temperature only, for callers that poll often
+    pub fn all_gpus_without_processes(&self) -> Vec<GpuInfo> {
+        self.collect(false)
+    }
+}
+
+impl GpuProviders {
+    // (used_gb, total_gb, percent) of the first GPU that reports VRAM, the
+    // shape get_gpu_usage has always returned
+    pub fn vram_usage(&self) -> Result<(f64, f64, f64), String> {
+        let gpu = self
+            .all_gpus_without_processes()
+            .into_iter()
+            .find(|gpu| gpu.vram_total_bytes.unwrap_or(0) > 0)
+            .ok_or("No GPU with VRAM information detected")?;
+
+        let gib = 1024.0 * 1024.0 * 1024.0;
+        let used = gpu.vram_used_bytes.unwrap_or(0) as f64;
+        let total = gpu.vram_total_bytes.unwrap_or(0) as f64;
+        Ok((used / gib, total / gib, used / total * 100.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    #[test]
+    fn parses_nvidia_smi_output() {
+        let gpus = parse_nvidia_gpus(
+            "0, GPU-aaa, NVIDIA GeForce RTX 3080, 37, 2048, 10240, 61\n\
+             1, GPU-bbb, Tesla T4, [N/A], 0, 15360, [N/A]\n",
+        );
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(gpus[0].0, "GPU-aaa");
+        assert_eq!(gpus[0].1.name, "NVIDIA GeForce RTX 3080");
+        assert_eq!(gpus[0].1.utilization_percent, Some(37.0));
+        assert_eq!(gpus[0].1.vram_used_bytes, Some(2048 * MIB));
+        assert_eq!(gpus[0].1.temperature_celsius, Some(61.0));
+        assert_eq!(gpus[1].1.utilization_percent, None);
+        assert_eq!(gpus[1].1.temperature_celsius, None);
+
+        let processes = parse_nvidia_processes("GPU-aaa, 4242, /usr/bin/ollama, 1500\n");
+        assert_eq!(
+            processes,
+            vec![(
+                "GPU-aaa".to_string(),
+                GpuProcess { pid: 4242, name: "/usr/bin/ollama".to_string(), vram_bytes: 1500 * MIB }
+            )]
+        );
+    }
+
+    #[test]
+    fn reads_amd_cards_from_sysfs() {
+        let fx = Fixture::new("amd");
+        fx.write("sys/class/drm/card1/device/vendor", "0x1002\n");
+        fx.write("sys/class/drm/card1/device/uevent", "DRIVER=amdgpu\nPCI_SLOT_NAME=0000:03:00.0\n");
+        fx.write("sys/class/drm/card1/device/gpu_busy_percent", "42\n");
+        fx.write("sys/class/drm/card1/device/mem_info_vram_used", "1073741824\n");
+        fx.write("sys/class/drm/card1/device/mem_info_vram_total", "8589934592\n");
+        fx.write("sys/class/drm/card1/device/hwmon/hwmon3/temp1_input", "55000\n");
+        // Connectors and other vendors are ignored
+        fx.write("sys/class/drm/card1-DP-1/device/vendor", "0x1002\n");
+        fx.write("sys/class/drm/card0/device/vendor", "0x8086\n");
+
+        fx.write("proc/100/comm", "blender\n");
+        let fdinfo = "pos:\t0\ndrm-driver:\tamdgpu\ndrm-pdev:\t0000:03:00.0\ndrm-client-id:\t7\ndrm-memory-vram:\t2048 KiB\n";
+        fx.write("proc/100/fdinfo/5", fdinfo);
+        fx.write("proc/100/fdinfo/6", fdinfo);
+        fx.write("proc/200/fdinfo/3", "pos:\t0\ndrm-pdev:\t0000:00:02.0\ndrm-client-id:\t1\ndrm-memory-vram:\t100 KiB\n");
+
+        let provider = AmdSysfsProvider::new(fx.dir.join("sys"), fx.dir.join("proc"));
+        assert!(provider.gpus(false).unwrap()[0].processes.is_empty());
+        let gpus = provider.gpus(true).unwrap();
+        assert_eq!(
+            gpus,
+            vec![GpuInfo {
+                index: 0,
+                vendor: GpuVendor::Amd,
+                name: "AMD GPU (card1)".to_string(),
+                utilization_percent: Some(42.0),
+                vram_used_bytes: Some(1 << 30),
+                vram_total_bytes: Some(8 << 30),
+                temperature_celsius: Some(55.0),
+                processes: vec![GpuProcess { pid: 100, name: "blender".to_string(), vram_bytes: 2048 * 1024 }],
+            }]
+        );
+
+        let intel = IntelSysfsProvider::new(fx.dir.join("sys"), fx.dir.join("proc")).gpus(true).unwrap();
+        assert_eq!(intel.len(), 1);
+        assert_eq!(intel[0].vendor, GpuVendor::Intel);
+        assert_eq!(intel[0].utilization_percent, None);
+    }
+
+    #[test]
+    fn fake_provider_numbers_gpus_across_providers() {
+        let fx = Fixture::new("fake");
+        fx.write(
+            "gpus.json",
+            r#"[
+                { "vendor": "Nvidia", "name": "Fake 1", "vram_used_bytes": 1, "vram_total_bytes": 4 },
+                { "vendor": "Amd", "name": "Fake 2", "utilization_percent": 12.5 }
+            ]"#,
+        );
+
+        let providers = GpuProviders(vec![
+            Box::new(FakeProvider::new(fx.dir.join("gpus.json"))),
+            Box::new(FakeProvider::new(fx.dir.join("missing.json"))),
+            Box::new(FakeProvider::new(fx.dir.join("gpus.json"))),
+        ]);
+        let gpus = providers.all_gpus();
+        assert_eq!(gpus.len(), 4);
+        assert_eq!(gpus.iter().map(|g| g.index).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!(gpus[1].utilization_percent, Some(12.5));
+        assert!(gpus[0].processes.is_empty());
+
+        let (_, _, percent) = providers.vram_usage().unwrap();
+        assert_eq!(percent, 25.0);
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::error::HubError;
+use crate::gpu::GpuProviders;
+use crate::metrics::{self, MetricsRegistry};
+use crate::sysmon::{self, SystemMonitorState};
+use crate::{get_app_data_dir, AppRegistry};
+
+// How often the sampler records a new value for every series
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+// How often the in-memory history is written to disk
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Each series keeps the same data at three resolutions: 5 s for the last hour,
+// 1 min for the last day and 10 min for the last week. Older points fall off
+// the front of each ring.
+struct Tier {
+    step_secs: i64,
+    capacity: usize,
+}
+
+const TIERS: [Tier; 3] = [
+    Tier { step_secs: 5, capacity: 720 },
+    Tier { step_secs: 60, capacity: 1440 },
+    Tier { step_secs: 600, capacity: 1008 },
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricPoint {
+    // Unix seconds at the start of the bucket
+    pub timestamp: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    // Raw samples merged into this point
+    pub count: u32,
+}
+
+impl MetricPoint {
+    fn merge(&mut self, other: &MetricPoint) {
+        let total = self.count + other.count;
+        self.avg = (self.avg * self.count as f64 + other.avg * other.count as f64) / total as f64;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count = total;
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Series {
+    tiers: Vec<VecDeque<MetricPoint>>,
+}
+
+impl Series {
+    fn record(&mut self, timestamp: i64, value: f64) {
+        self.tiers.resize_with(TIERS.len(), VecDeque::new);
+
+        for (tier, points) in TIERS.iter().zip(self.tiers.iter_mut()) {
+            let sample = MetricPoint {
+                timestamp: timestamp - timestamp.rem_euclid(tier.step_secs),
+                avg: value,
+                min: value,
+                max: value,
+                count: 1,
+            };
+            match points.back_mut() {
+                Some(last) if last.timestamp == sample.timestamp => last.merge(&sample),
+                _ => points.push_back(sample),
+            }
+            while points.len() > tier.capacity {
+                points.pop_front();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MetricsHistory {
+    series: HashMap<String, Series>,
+}
+
+pub type HistoryRegistry = Mutex<MetricsHistory>;
+
+impl MetricsHistory {
+    pub fn record(&mut self, metric: &str, timestamp: i64, value: f64) {
+        self.series
+            .entry(metric.to_string())
+            .or_default()
+            .record(timestamp, value);
+    }
+
+    pub fn metric_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.series.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // Drop every series of a removed app
+    pub fn forget_app(&mut self, app_id: &str) {
+        let prefix = format!("app.{}.", app_id);
+        self.series.retain(|name, _| !name.starts_with(&prefix));
+    }
+
+    // Points of `metric` between `from` and `to` (unix seconds, inclusive).
+    // Uses the finest tier that still reaches back to `from` and, when a
+    // coarser `resolution` (seconds) is asked for, merges points into buckets
+    // of that size.
+    pub fn query(&self, metric: &str, from: i64, to: i64, resolution: Option<i64>, now: i64) -> Vec<MetricPoint> {
+        let series = match self.series.get(metric) {
+            Some(series) => series,
+            None => return Vec::new(),
+        };
+
+        let tier_index = TIERS
+            .iter()
+            .position(|tier| now - from <= tier.step_secs * tier.capacity as i64)
+            .unwrap_or(TIERS.len() - 1);
+        let points = match series.tiers.get(tier_index) {
+            Some(points) => points,
+            None => return Vec::new(),
+        };
+        let step = TIERS[tier_index].step_secs;
+        let in_range = points
+            .iter()
+            .filter(|p| p.timestamp <= to && p.timestamp + step > from);
+
+        let bucket = match resolution {
+            Some(r) if r > step => r,
+            _ => return in_range.cloned().collect(),
+        };
+
+        let mut merged: Vec<MetricPoint> = Vec::new();
+        for point in in_range {
+            let start = point.timestamp - point.timestamp.rem_euclid(bucket);
+            match merged.last_mut() {
+                Some(last) if last.timestamp == start => last.merge(point),
+                _ => merged.push(MetricPoint {
+                    timestamp: start,
+                    ..point.clone()
+                }),
+            }
+        }
+        merged
+    }
+}
+
+fn get_history_path(app_handle: &AppHandle) -> Result<PathBuf, HubError> {
+    Ok(get_app_data_dir(app_handle)?.join("metrics-history.json"))
+}
+
+pub fn load_history(app_handle: &AppHandle) -> Result<MetricsHistory, HubError> {
+    let path = get_history_path(app_handle)?;
+    if !path.exists() {
+        return Ok(MetricsHistory::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| HubError::io("Failed to read metrics history", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| HubError::parse(format!("Failed to parse metrics history: {}", e)))
+}
+
+fn serialize_history(history: &MetricsHistory) -> Result<String, HubError> {
+    serde_json::to_string(history)
+        .map_err(|e| HubError::from(format!("Failed to serialize metrics history: {}", e)))
+}
+
+fn save_history(app_handle: &AppHandle, content: String) -> Result<(), HubError> {
+    let path = get_history_path(app_handle)?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)
+        .and_then(|_| fs::rename(&tmp_path, &path))
+        .map_err(|e| HubError::io("Failed to write metrics history", e))
+}
+
+fn sample_once(app_handle: &AppHandle, monitor: &mut SystemMonitorState) -> Vec<(String, f64)> {
+    let system = sysmon::snapshot(monitor);
+    let mut values = vec![
+        ("system.cpu_percent".to_string(), system.cpu.total_percent),
+        ("system.ram_percent".to_string(), system.memory.percent),
+        ("system.swap_percent".to_string(), system.swap.percent),
+    ];
+
+    let gpus = app_handle.state::<GpuProviders>().all_gpus_without_processes();
+    for gpu in gpus {
+        if let Some(percent) = gpu.utilization_percent {
+            values.push((format!("gpu.{}.utilization_percent", gpu.index), percent));
+        }
+        if let Some(used) = gpu.vram_used_bytes {
+            values.push((format!("gpu.{}.vram_used_bytes", gpu.index), used as f64));
+        }
+    }
+
+    let apps: Vec<_> = match app_handle.state::<AppRegistry>().lock() {
+        Ok(apps) => apps.values().cloned().collect(),
+        Err(_) => return values,
+    };
+    let app_metrics = match metrics::sample_apps(&app_handle.state::<MetricsRegistry>(), &apps, true) {
+        Ok(app_metrics) => app_metrics,
+        Err(_) => return values,
+    };
+    for (app_id, m) in app_metrics {
+        values.push((format!("app.{}.cpu_percent", app_id), m.cpu_percent));
+        values.push((format!("app.{}.rss_bytes", app_id), m.rss_bytes as f64));
+        values.push((format!("app.{}.threads", app_id), m.threads as f64));
+        values.push((format!("app.{}.open_fds", app_id), m.open_fds as f64));
+    }
+    values
+}
+
+// Background thread recording system and per-app metrics every
+// SAMPLE_INTERVAL and persisting them every FLUSH_INTERVAL
+pub fn start_history_sampler(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let mut last_flush = Instant::now();
+        // Our own CPU baseline, so the rate covers the time since our last sample
+        let mut monitor = SystemMonitorState::default();
+
+        loop {
+            let values = sample_once(&app_handle, &mut monitor);
+            let now = chrono::Utc::now().timestamp();
+
+            let history = app_handle.state::<HistoryRegistry>();
+            let flush = match history.lock() {
+                Ok(mut history) => {
+                    for (metric, value) in values {
+                        history.record(&metric, now, value);
+                    }
+
+                    if last_flush.elapsed() >= FLUSH_INTERVAL {
+                        last_flush = Instant::now();
+                        Some(serialize_history(&history))
+                    } else {
+                        None
+                    }
+                }
+                Err(_) => None,
+            };
+
+            // Write with the lock released, queries shouldn't wait on the disk
+            if let Some(content) = flush {
+                if let Err(e) = content.and_then(|content| save_history(&app_handle, content)) {
+                    eprintln!("{}", e);
+                }
+            }
+
+            thread::sleep(SAMPLE_INTERVAL);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(points: &[MetricPoint]) -> Vec<(i64, f64)> {
+        points.iter().map(|p| (p.timestamp, p.avg)).collect()
+    }
+
+    #[test]
+    fn downsamples_into_coarser_tiers() {
+        let mut history = MetricsHistory::default();
+        for i in 0..24 {
+            history.record("m", 6000 + i * 5, i as f64);
+        }
+
+        let series = &history.series["m"];
+        assert_eq!(series.tiers[0].len(), 24);
+        assert_eq!(series.tiers[1].len(), 2);
+        assert_eq!(series.tiers[2].len(), 1);
+
+        let first_minute = &series.tiers[1][0];
+        assert_eq!(first_minute.timestamp, 6000);
+        assert_eq!(first_minute.count, 12);
+        assert_eq!(first_minute.avg, 5.5);
+        assert_eq!(first_minute.min, 0.0);
+        assert_eq!(first_minute.max, 11.0);
+        assert_eq!(series.tiers[2][0].count, 24);
+    }
+
+    #[test]
+    fn rings_drop_the_oldest_points() {
+        let mut history = MetricsHistory::default();
+        for i in 0..(TIERS[0].capacity as i64 + 10) {
+            history.record("m", i * 5, 1.0);
+        }
+
+        let raw = &history.series["m"].tiers[0];
+        assert_eq!(raw.len(), TIERS[0].capacity);
+        assert_eq!(raw.front().unwrap().timestamp, 50);
+    }
+
+    #[test]
+    fn query_picks_a_tier_and_rebuckets() {
+        let mut history = MetricsHistory::default();
+        let now = 100_200;
+        for i in 0..120 {
+            history.record("m", now - 600 + i * 5, (i % 2) as f64);
+        }
+
+        // The last 10 minutes come from the 5 s tier
+        let raw = history.query("m", now - 600, now, None, now);
+        assert_eq!(raw.len(), 120);
+
+        // Asking for 5 minute buckets merges them
+        let merged = history.query("m", now - 600, now, Some(300), now);
+        assert_eq!(values(&merged), vec![(99_600, 0.5), (99_900, 0.5)]);
+
+        // A day back only the 1 minute tier reaches
+        let day = history.query("m", now - 86_400, now, None, now);
+        assert_eq!(day.len(), 10);
+        assert!(day.iter().all(|p| p.count == 12));
+
+        assert!(history.query("missing", now - 600, now, None, now).is_empty());
+    }
+
+    #[test]
+    fn forgets_removed_apps() {
+        let mut history = MetricsHistory::default();
+        history.record("app.a.cpu_percent", 0, 1.0);
+        history.record("app.ab.cpu_percent", 0, 1.0);
+        history.record("system.ram_percent", 0, 1.0);
+
+        history.forget_app("a");
+        assert_eq!(history.metric_names(), vec!["app.ab.cpu_percent", "system.ram_percent"]);
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::{self, HubError};
+use crate::services::{ServiceDefinition, ServiceScope};
+use crate::validation::{self, FieldErrorCode};
+
+// Entries returned by get_service_journal when no count is given
+const DEFAULT_LINES: u32 = 200;
+// Highest syslog priority (debug)
+const MAX_PRIORITY: u8 = 7;
+
+pub const SERVICE_JOURNAL_EVENT: &str = "service-journal";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JournalEntry {
+    // RFC 3339, from __REALTIME_TIMESTAMP
+    pub timestamp: String,
+    // Syslog priority, 0 (emerg) to 7 (debug)
+    pub priority: Option<u8>,
+    pub pid: Option<u32>,
+    // SYSLOG_IDENTIFIER, usually the program name
+    pub identifier: Option<String>,
+    pub message: String,
+    // Opaque position in the journal
+    pub cursor: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceJournalEvent {
+    pub unit: String,
+    pub entry: JournalEntry,
+}
+
+// journalctl processes following a unit, keyed by unit name
+pub type JournalFollowers = Mutex<HashMap<String, Child>>;
+
+fn field<'a>(record: &'a Value, key: &str) -> Option<&'a str> {
+    record.get(key).and_then(Value::as_str)
+}
+
+// MESSAGE is a string, or an array of bytes when it isn't valid UTF-8 or
+// contains control characters
+fn message(record: &Value) -> String {
+    match record.get("MESSAGE") {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(bytes)) => {
+            let bytes: Vec<u8> = bytes.iter().filter_map(|b| b.as_u64()).map(|b| b as u8).collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => String::new(),
+    }
+}
+
+// One line of `journalctl --output=json`
+pub fn parse_entry(line: &str) -> Result<JournalEntry, HubError> {
+    let record: Value = serde_json::from_str(line)
+        .map_err(|e| HubError::parse(format!("Failed to parse journal entry: {}", e)))?;
+
+    let timestamp = field(&record, "__REALTIME_TIMESTAMP")
+        .and_then(|t| t.parse::<i64>().ok())
+        .and_then(chrono::DateTime::from_timestamp_micros)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default();
+
+    Ok(JournalEntry {
+        timestamp,
+        priority: field(&record, "PRIORITY").and_then(|p| p.parse().ok()),
+        pid: field(&record, "_PID").and_then(|p| p.parse().ok()),
+        identifier: field(&record, "SYSLOG_IDENTIFIER").map(str::to_string),
+        message: message(&record),
+        cursor: field(&record, "__CURSOR").unwrap_or_default().to_string(),
+    })
+}
+
+fn journalctl(service: &ServiceDefinition, priority: Option<u8>) -> Result<Command, HubError> {
+    let mut cmd = Command::new("journalctl");
+    match service.scope {
+        ServiceScope::System => cmd.arg("--unit"),
+        ServiceScope::User => cmd.arg("--user-unit"),
+    };
+    cmd.arg(&service.unit).args(["--output=json", "--no-pager"]);
+
+    if let Some(priority) = priority {
+        if priority > MAX_PRIORITY {
+            return Err(HubError::validation(vec![validation::error(
+                "priority",
+                FieldErrorCode::InvalidValue,
+                format!("Journal priority must be between 0 and {}", MAX_PRIORITY),
+            )]));
+        }
+        cmd.arg(format!("--priority={}", priority));
+    }
+    Ok(cmd)
+}
+
+// The last `lines` entries of a unit, oldest first. `since` takes anything
+// journalctl --since does ("2024-05-01 10:00", "-1h", "today"); `priority`
+// keeps entries at that level or more severe.
+pub fn read(
+    service: &ServiceDefinition,
+    lines: Option<u32>,
+    since: Option<&str>,
+    priority: Option<u8>,
+) -> Result<Vec<JournalEntry>, HubError> {
+    let mut cmd = journalctl(service, priority)?;
+    cmd.arg(format!("--lines={}", lines.unwrap_or(DEFAULT_LINES)));
+    if let Some(since) = since {
+        cmd.arg(format!("--since={}", since));
+    }
+
+    let output = error::run(&mut cmd, &format!("read the journal of {}", service.unit))?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_entry)
+        .collect()
+}
+
+// Stream new entries of a unit as SERVICE_JOURNAL_EVENT until unfollow is
+// called. Following a unit that is already followed is a no-op.
+pub fn follow(app_handle: &AppHandle, service: &ServiceDefinition, priority: Option<u8>) -> Result<(), HubError> {
+    let followers = app_handle.state::<JournalFollowers>();
+    let mut followers = followers.lock()?;
+    if let Some(child) = followers.get_mut(&service.unit) {
+        if matches!(child.try_wait(), Ok(None)) {
+            return Ok(());
+        }
+    }
+
+    let mut cmd = journalctl(service, priority)?;
+    cmd.args(["--follow", "--lines=0"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| error::spawn_error("journalctl", &format!("follow the journal of {}", service.unit), e))?;
+
+    let stdout = child.stdout.take();
+    let pid = child.id();
+    followers.insert(service.unit.clone(), child);
+
+    if let Some(stdout) = stdout {
+        let app_handle = app_handle.clone();
+        let unit = service.unit.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                match parse_entry(&line) {
+                    Ok(entry) => {
+                        let event = ServiceJournalEvent { unit: unit.clone(), entry };
+                        let _ = app_handle.emit(SERVICE_JOURNAL_EVENT, &event);
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+
+            // journalctl exited on its own; forget it unless it was replaced already
+            if let Ok(mut followers) = app_handle.state::<JournalFollowers>().lock() {
+                if followers.get(&unit).map(|c| c.id()) == Some(pid) {
+                    if let Some(mut child) = followers.remove(&unit) {
+                        let _ = child.wait();
+                    }
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+pub fn unfollow(app_handle: &AppHandle, unit: &str) -> Result<(), HubError> {
+    let child = app_handle.state::<JournalFollowers>().lock()?.remove(unit);
+    if let Some(mut child) = child {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_journalctl_json() {
+        let entry = parse_entry(
+            r#"{"__CURSOR":"s=abc;i=1","__REALTIME_TIMESTAMP":"1700000000000000","PRIORITY":"3","_PID":"812","SYSLOG_IDENTIFIER":"ollama","MESSAGE":"listen tcp: address already in use"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            entry,
+            JournalEntry {
+                timestamp: "2023-11-14T22:13:20+00:00".to_string(),
+                priority: Some(3),
+                pid: Some(812),
+                identifier: Some("ollama".to_string()),
+                message: "listen tcp: address already in use".to_string(),
+                cursor: "s=abc;i=1".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_binary_messages_and_missing_fields() {
+        let entry = parse_entry(r#"{"__CURSOR":"c","MESSAGE":[104,105,27,91,48,109]}"#).unwrap();
+        assert_eq!(entry.message, "hi\u{1b}[0m");
+        assert_eq!(entry.priority, None);
+        assert_eq!(entry.pid, None);
+        assert_eq!(entry.timestamp, "");
+
+        assert!(matches!(parse_entry("not json"), Err(HubError::Parse { .. })));
+    }
+
+    #[test]
+    fn rejects_out_of_range_priorities() {
+        let service = ServiceDefinition {
+            id: "ollama".to_string(),
+            display_name: "Ollama".to_string(),
+            unit: "ollama.service".to_string(),
+            scope: ServiceScope::System,
+            pkexec: false,
+        };
+        assert!(journalctl(&service, Some(MAX_PRIORITY)).is_ok());
+        match journalctl(&service, Some(MAX_PRIORITY + 1)) {
+            Err(HubError::Validation { fields, .. }) => assert_eq!(fields[0].field, "priority"),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -1,18 +1,68 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::process::{Command, Child, Stdio};
-use tauri::{State, Manager, AppHandle};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tauri::{State, Manager, AppHandle, Emitter};
 use std::sync::{Mutex, Arc};
 use std::fs;
 use std::io::Write;
-use regex::Regex;
 use std::time::SystemTime;
 use notify::{Watcher, RecursiveMode};
 use std::sync::mpsc::channel;
 use std::thread;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use tauri_plugin_notification::NotificationExt;
+use std::time::Duration;
+
+mod alerts;
+mod discovery;
+mod error;
+mod gpu;
+mod history;
+mod journal;
+mod logs;
+mod metrics;
+mod process;
+mod recorder;
+mod profiles;
+mod registry;
+mod restart;
+mod services;
+mod settings;
+mod stt;
+mod supervisor;
+mod sysmon;
+mod systemd;
+#[cfg(test)]
+mod test_util;
+mod transcriber;
+mod transcription;
+mod transcripts;
+mod validation;
+mod watcher;
+
+use alerts::AlertRule;
+use discovery::DiscoveredApp;
+use error::HubError;
+use gpu::{GpuInfo, GpuProviders};
+use history::{HistoryRegistry, MetricPoint};
+use journal::{JournalEntry, JournalFollowers};
+use logs::{LogLine, LogRegistry};
+use metrics::{AppMetrics, MetricsRegistry};
+use process::ProcessRegistry;
+use recorder::{AudioDevice, InputSource, Recorder};
+use profiles::LaunchProfile;
+use registry::{load_registry, save_registry, save_registry_status};
+use restart::{RestartPolicy, RestartRegistry};
+use services::{ServiceAction, ServiceStatus};
+use settings::SettingsState;
+use stt::{SttEngine, SttSettings};
+use supervisor::emit_app_status;
+use sysmon::{SystemMonitorRegistry, SystemSnapshot};
+use systemd::SystemdBus;
+use transcription::TranscriptionJobs;
+use transcripts::{TranscriptArchive, TranscriptRecord, TranscriptSummary};
+use validation::{FieldError, ValidationMode};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TauriApp {
@@ -23,6 +73,47 @@ pub struct TauriApp {
     pub executable: String,
     pub icon: Option<String>,
     pub status: AppStatus,
+    // Seconds to wait after SIGTERM before the process group gets SIGKILL
+    #[serde(default = "default_stop_grace_period_secs")]
+    pub stop_grace_period_secs: u64,
+    // How the most recent run of the app ended, filled in by the supervisor
+    #[serde(default)]
+    pub last_exit: Option<AppExit>,
+    // Persisted so a restarted hub can find the process again
+    #[serde(default)]
+    pub pid: Option<u32>,
+    #[serde(default)]
+    pub start_time: Option<u64>,
+    #[serde(default)]
+    pub profiles: Vec<LaunchProfile>,
+    // Profile used when launch_app is called without one
+    #[serde(default)]
+    pub default_profile: Option<String>,
+    // Profile the current (or most recent) run was started with
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+    // Why the app is in its current status, e.g. a pending restart or crash loop
+    #[serde(default)]
+    pub status_reason: Option<String>,
+    // Version from tauri.conf.json, kept up to date by the app watcher
+    #[serde(default)]
+    pub version: Option<String>,
+    // Set by the app watcher while the app's path doesn't exist
+    #[serde(default)]
+    pub missing: bool,
+}
+
+fn default_stop_grace_period_secs() -> u64 {
+    5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppExit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub exited_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,8 +136,7 @@ pub enum RecordingStatus {
 #[derive(Debug)]
 pub struct RecordingState {
     pub status: RecordingStatus,
-    pub process: Option<Child>,
-    pub pid: Option<u32>,
+    pub recorder: Option<Recorder>,
     pub current_file: Option<String>,
 }
 
@@ -61,42 +151,72 @@ pub struct OssecState {
 
 type OssecRegistry = Mutex<OssecState>;
 
-fn get_registry_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+// Expand a leading '~' to the user's home directory
+fn expand_home(path: &std::path::Path) -> PathBuf {
+    let path_str = path.to_string_lossy();
+    if path_str.starts_with("~") {
+        if let Ok(home) = std::env::var("HOME") {
+            let rest = path_str.trim_start_matches('~');
+            return PathBuf::from(home).join(rest.trim_start_matches('/'));
+        }
+    }
+    path.to_path_buf()
+}
+
+fn get_app_data_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, HubError> {
     let app_dir = app_handle.path().app_data_dir()
-        .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+        .map_err(|e| HubError::Io { message: format!("Failed to get app data dir: {}", e) })?;
     fs::create_dir_all(&app_dir)
-        .map_err(|e| format!("Failed to create app data dir: {}", e))?;
-    Ok(app_dir.join("registry.json"))
+        .map_err(|e| HubError::io("Failed to create app data dir", e))?;
+    Ok(app_dir)
 }
 
-fn load_registry(app_handle: &tauri::AppHandle) -> Result<HashMap<String, TauriApp>, String> {
-    let registry_path = get_registry_path(app_handle)?;
-    
-    if !registry_path.exists() {
-        return Ok(HashMap::new());
-    }
-    
-    let content = fs::read_to_string(&registry_path)
-        .map_err(|e| format!("Failed to read registry: {}", e))?;
-    
-    serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse registry: {}", e))
+// A registered app together with the current resource usage of its process
+// tree, None while it isn't running
+#[derive(Debug, Clone, Serialize)]
+pub struct AppWithMetrics {
+    #[serde(flatten)]
+    pub app: TauriApp,
+    pub metrics: Option<AppMetrics>,
 }
 
-fn save_registry(app_handle: &tauri::AppHandle, apps: &HashMap<String, TauriApp>) -> Result<(), String> {
-    let registry_path = get_registry_path(app_handle)?;
-    
-    let content = serde_json::to_string_pretty(apps)
-        .map_err(|e| format!("Failed to serialize registry: {}", e))?;
+#[tauri::command]
+async fn get_registered_apps(
+    registry: State<'_, AppRegistry>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<AppWithMetrics>, HubError> {
+    let apps: Vec<TauriApp> = registry.lock()?.values().cloned().collect();
+    // The listing shouldn't wait for, or fail with, the metrics
+    let mut metrics = match metrics::sample(&app_handle, apps.clone(), false).await {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            eprintln!("Failed to sample app metrics: {}", e);
+            HashMap::new()
+        }
+    };
     
-    fs::write(&registry_path, content)
-        .map_err(|e| format!("Failed to write registry: {}", e))
+    Ok(apps
+        .into_iter()
+        .map(|app| AppWithMetrics {
+            metrics: metrics.remove(&app.id),
+            app,
+        })
+        .collect())
 }
 
 #[tauri::command]
-async fn get_registered_apps(registry: State<'_, AppRegistry>) -> Result<Vec<TauriApp>, String> {
-    let apps = registry.lock().map_err(|e| e.to_string())?;
-    Ok(apps.values().cloned().collect())
+async fn get_app_metrics(
+    app_id: String,
+    registry: State<'_, AppRegistry>,
+    app_handle: tauri::AppHandle,
+) -> Result<Option<AppMetrics>, HubError> {
+    let app = registry.lock()?
+        .get(&app_id)
+        .cloned()
+        .ok_or_else(|| HubError::not_found(format!("App with id '{}' not found", app_id)))?;
+    
+    let mut metrics = metrics::sample(&app_handle, vec![app], true).await?;
+    Ok(metrics.remove(&app_id))
 }
 
 #[tauri::command]
@@ -104,267 +224,469 @@ async fn register_app(
     app: TauriApp,
     registry: State<'_, AppRegistry>,
     app_handle: tauri::AppHandle,
-) -> Result<(), String> {
-    let mut apps = registry.lock().map_err(|e| e.to_string())?;
-    apps.insert(app.id.clone(), app);
+) -> Result<(), HubError> {
+    let mut apps = registry.lock()?;
+    
+    insert_new_app(&mut apps, app)?;
     save_registry(&app_handle, &apps)?;
+    watcher::request_rescan(&app_handle);
     Ok(())
 }
 
+fn insert_new_app(apps: &mut HashMap<String, TauriApp>, mut app: TauriApp) -> Result<(), HubError> {
+    let errors = validation::validate_app(&app, apps, ValidationMode::Register);
+    if !errors.is_empty() {
+        return Err(HubError::validation(errors));
+    }
+    
+    // Runtime state is owned by the hub, not by whoever registers the app
+    app.status = AppStatus::Stopped;
+    app.status_reason = None;
+    app.last_exit = None;
+    app.pid = None;
+    app.start_time = None;
+    app.active_profile = None;
+    app.missing = false;
+    profiles::ensure_profiles(&mut app);
+    
+    apps.insert(app.id.clone(), app);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkRegisterResult {
+    pub app_id: String,
+    pub error: Option<HubError>,
+}
+
 #[tauri::command]
-async fn launch_app(
-    app_id: String,
+async fn discover_apps(
+    roots: Option<Vec<PathBuf>>,
+    registry: State<'_, AppRegistry>,
+    settings: State<'_, SettingsState>,
+) -> Result<Vec<DiscoveredApp>, HubError> {
+    let roots = match roots {
+        Some(roots) => roots,
+        None => settings.lock()?.discovery_roots.clone(),
+    };
+    let registered = registry.lock()?.clone();
+    
+    tauri::async_runtime::spawn_blocking(move || discovery::discover(&roots, &registered))
+        .await
+        .map_err(|e| HubError::from(format!("Discovery failed: {}", e)))
+}
+
+// Register the apps picked from discover_apps in one go. Each app is validated
+// on its own, so one bad entry doesn't keep the others out.
+#[tauri::command]
+async fn register_discovered_apps(
+    apps: Vec<TauriApp>,
     registry: State<'_, AppRegistry>,
     app_handle: tauri::AppHandle,
-) -> Result<(), String> {
-    let mut apps = registry.lock().map_err(|e| e.to_string())?;
+) -> Result<Vec<BulkRegisterResult>, HubError> {
+    let mut registered = registry.lock()?;
+    
+    let results: Vec<BulkRegisterResult> = apps
+        .into_iter()
+        .map(|app| BulkRegisterResult {
+            app_id: app.id.clone(),
+            error: insert_new_app(&mut registered, app).err(),
+        })
+        .collect();
     
-    if let Some(app) = apps.get_mut(&app_id) {
-        // Expand '~' in path if present
-        let mut workdir = app.path.clone();
-        let workdir_str = workdir.to_string_lossy();
-        if workdir_str.starts_with("~") {
-            if let Ok(home) = std::env::var("HOME") {
-                let rest = workdir_str.trim_start_matches('~');
-                workdir = PathBuf::from(home).join(rest.trim_start_matches('/'));
-            }
+    if results.iter().any(|r| r.error.is_none()) {
+        save_registry(&app_handle, &registered)?;
+        watcher::request_rescan(&app_handle);
+    }
+    Ok(results)
+}
+
+#[tauri::command]
+async fn get_discovery_roots(settings: State<'_, SettingsState>) -> Result<Vec<PathBuf>, HubError> {
+    let settings = settings.lock()?;
+    Ok(settings.discovery_roots.clone())
+}
+
+#[tauri::command]
+async fn set_discovery_roots(
+    roots: Vec<PathBuf>,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    let mut settings = settings.lock()?;
+    settings.discovery_roots = roots;
+    settings::save_settings(&app_handle, &settings)?;
+    watcher::request_rescan(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+async fn update_app(
+    mut app: TauriApp,
+    registry: State<'_, AppRegistry>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    let mut apps = registry.lock()?;
+    
+    // Editors that don't know about launch profiles keep the existing ones
+    if let Some(existing) = apps.get(&app.id) {
+        if app.profiles.is_empty() {
+            app.profiles = existing.profiles.clone();
+            app.default_profile = existing.default_profile.clone();
         }
+    }
+    
+    let errors = validation::validate_app(&app, &apps, ValidationMode::Update);
+    if !errors.is_empty() {
+        return Err(HubError::validation(errors));
+    }
+    
+    let existing = apps.get(&app.id)
+        .ok_or_else(|| HubError::not_found(format!("App with id '{}' not found", app.id)))?;
+    app.status = existing.status.clone();
+    app.status_reason = existing.status_reason.clone();
+    app.last_exit = existing.last_exit.clone();
+    app.pid = existing.pid;
+    app.start_time = existing.start_time;
+    app.active_profile = existing.active_profile.clone();
+    if app.version.is_none() {
+        app.version = existing.version.clone();
+    }
+    // Validation just confirmed the path exists
+    app.missing = false;
+    profiles::ensure_profiles(&mut app);
+    
+    apps.insert(app.id.clone(), app);
+    save_registry(&app_handle, &apps)?;
+    watcher::request_rescan(&app_handle);
+    Ok(())
+}
 
-        // Build command, supporting dev commands with pnpm/cargo
-        let mut cmd = Command::new(&app.executable);
-        match app.executable.as_str() {
-            // Support running package.json scripts like: pnpm run tauri:dev
-            "pnpm" => {
-                cmd.arg("run").arg("tauri:dev");
+// Spawn the app with the given (or default) profile and start capturing its
+// output. Shared by launch_app and the restart supervisor.
+fn start_app(app_handle: &AppHandle, app_id: &str, profile: Option<&str>) -> Result<(), HubError> {
+    let registry = app_handle.state::<AppRegistry>();
+    let processes = app_handle.state::<ProcessRegistry>();
+    let restarts = app_handle.state::<RestartRegistry>();
+    let mut apps = registry.lock()?;
+    let mut processes = processes.lock()?;
+    
+    if let Some(app) = apps.get_mut(app_id) {
+        if let Some(process) = processes.get_mut(app_id) {
+            if matches!(process.try_wait(), Ok(None)) {
+                return Err(HubError::invalid_state(format!("App '{}' is already running", app.name)));
             }
-            // Support cargo tauri dev
-            "cargo" => {
-                cmd.arg("tauri").arg("dev");
-            }
-            _ => {}
         }
-        let result = cmd.current_dir(&workdir).spawn();
+
+        let launch_profile = profiles::select_profile(app, profile).map_err(HubError::not_found)?;
+        let profile_name = launch_profile.name.clone();
+        let executable = launch_profile.executable.clone();
+        let mut cmd = profiles::build_command(app, launch_profile);
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let result = process::spawn_in_new_group(&mut cmd);
             
         match result {
-            Ok(_) => {
+            Ok(mut process) => {
+                logs::capture_output(app_handle, app_id, &mut process);
+                app.pid = Some(process.pid);
+                app.start_time = process.start_time;
+                app.active_profile = Some(profile_name);
+                processes.insert(app_id.to_string(), process);
+                if let Ok(mut restarts) = restarts.lock() {
+                    restarts.entry(app_id.to_string()).or_default().record_start();
+                }
                 app.status = AppStatus::Running;
-                save_registry(&app_handle, &apps)?;
+                app.status_reason = None;
+                emit_app_status(app_handle, app);
+                save_registry_status(app_handle, &apps)?;
                 Ok(())
             }
             Err(e) => {
+                let err = error::spawn_error(
+                    &executable,
+                    &format!("launch app with profile '{}'", profile_name),
+                    e,
+                );
                 app.status = AppStatus::Error;
-                save_registry(&app_handle, &apps)?;
-                Err(format!("Failed to launch app: {}", e))
+                app.status_reason = Some(err.to_string());
+                emit_app_status(app_handle, app);
+                save_registry_status(app_handle, &apps)?;
+                Err(err)
             }
         }
     } else {
-        Err(format!("App with id '{}' not found", app_id))
+        Err(HubError::not_found(format!("App with id '{}' not found", app_id)))
     }
 }
 
+#[tauri::command]
+async fn launch_app(
+    app_id: String,
+    profile: Option<String>,
+    restarts: State<'_, RestartRegistry>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    // A manual launch starts a fresh restart budget
+    restarts.lock()?
+        .entry(app_id.clone())
+        .or_default()
+        .reset();
+    
+    start_app(&app_handle, &app_id, profile.as_deref())
+}
+
 #[tauri::command]
 async fn stop_app(
     app_id: String,
     registry: State<'_, AppRegistry>,
+    processes: State<'_, ProcessRegistry>,
+    restarts: State<'_, RestartRegistry>,
     app_handle: tauri::AppHandle,
-) -> Result<(), String> {
-    let mut apps = registry.lock().map_err(|e| e.to_string())?;
+) -> Result<(), HubError> {
+    // Cancel any restart that is waiting out its backoff
+    restarts.lock()?
+        .entry(app_id.clone())
+        .or_default()
+        .reset();
+    
+    let grace_period = {
+        let apps = registry.lock()?;
+        let app = apps.get(&app_id)
+            .ok_or_else(|| HubError::not_found(format!("App with id '{}' not found", app_id)))?;
+        Duration::from_secs(app.stop_grace_period_secs)
+    };
     
+    let process = processes.lock()?.remove(&app_id);
+    
+    // Waiting for the process tree to exit can take the whole grace period,
+    // so keep it off the async runtime
+    let result = match process {
+        Some(process) => tauri::async_runtime::spawn_blocking(move || {
+            process::terminate(process, grace_period)
+        })
+        .await
+        .map_err(|e| HubError::from(format!("Failed to stop app: {}", e)))?
+        .map(Some),
+        None => Ok(None),
+    };
+    
+    let mut apps = registry.lock()?;
     if let Some(app) = apps.get_mut(&app_id) {
-        app.status = AppStatus::Stopped;
-        save_registry(&app_handle, &apps)?;
-        // Note: In a real implementation, you'd track process IDs to properly terminate apps
-        Ok(())
-    } else {
-        Err(format!("App with id '{}' not found", app_id))
+        app.status = if result.is_ok() { AppStatus::Stopped } else { AppStatus::Error };
+        app.status_reason = result.as_ref().err().map(|e| e.to_string());
+        if let Ok(Some(exit)) = &result {
+            app.last_exit = supervisor::stopped_exit(exit);
+        }
+        app.pid = None;
+        app.start_time = None;
+        emit_app_status(&app_handle, app);
+        save_registry_status(&app_handle, &apps)?;
     }
+    
+    result.map(|_| ())
+}
+
+#[tauri::command]
+async fn get_app_logs(
+    app_id: String,
+    since: Option<u64>,
+    logs: State<'_, LogRegistry>,
+) -> Result<Vec<LogLine>, HubError> {
+    let logs = logs.lock()?;
+    Ok(logs.get(&app_id).map(|log| log.since(since)).unwrap_or_default())
 }
 
 #[tauri::command]
 async fn remove_app(
     app_id: String,
     registry: State<'_, AppRegistry>,
+    logs: State<'_, LogRegistry>,
     app_handle: tauri::AppHandle,
-) -> Result<(), String> {
-    let mut apps = registry.lock().map_err(|e| e.to_string())?;
+) -> Result<(), HubError> {
+    let mut apps = registry.lock()?;
     
     if apps.remove(&app_id).is_some() {
+        logs.lock()?.remove(&app_id);
+        if let Ok(mut history) = app_handle.state::<HistoryRegistry>().lock() {
+            history.forget_app(&app_id);
+        }
         save_registry(&app_handle, &apps)?;
+        watcher::request_rescan(&app_handle);
         Ok(())
     } else {
-        Err(format!("App with id '{}' not found", app_id))
+        Err(HubError::not_found(format!("App with id '{}' not found", app_id)))
     }
 }
 
+// Points of a recorded metric such as "system.ram_percent" or
+// "app.<id>.cpu_percent" between two unix timestamps
+#[tauri::command]
+async fn query_metrics(
+    metric: String,
+    from: i64,
+    to: i64,
+    resolution: Option<i64>,
+    history: State<'_, HistoryRegistry>,
+) -> Result<Vec<MetricPoint>, HubError> {
+    let history = history.lock()?;
+    let now = chrono::Utc::now().timestamp();
+    Ok(history.query(&metric, from, to, resolution, now))
+}
+
+#[tauri::command]
+async fn list_metrics(history: State<'_, HistoryRegistry>) -> Result<Vec<String>, HubError> {
+    let history = history.lock()?;
+    Ok(history.metric_names())
+}
+
 // Speech-to-text commands
 
 #[tauri::command]
-async fn get_recording_status(recording: State<'_, RecordingRegistry>) -> Result<RecordingStatus, String> {
-    let state = recording.lock().map_err(|e| e.to_string())?;
+async fn get_recording_status(recording: State<'_, RecordingRegistry>) -> Result<RecordingStatus, HubError> {
+    let state = recording.lock()?;
     Ok(state.status.clone())
 }
 
 #[tauri::command]
-async fn start_recording(recording: State<'_, RecordingRegistry>) -> Result<(), String> {
-    let mut state = recording.lock().map_err(|e| e.to_string())?;
+async fn start_recording(
+    recording: State<'_, RecordingRegistry>,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    let mut state = recording.lock()?;
     
     if !matches!(state.status, RecordingStatus::Idle) {
-        return Err("Recording already in progress".to_string());
+        return Err(HubError::invalid_state("Recording already in progress"));
+    }
+    
+    // Don't let the user talk for minutes only to find Whisper can't run
+    let stt_settings = settings.lock()?.stt.clone();
+    let errors = stt::validate(&stt_settings);
+    if !errors.is_empty() {
+        return Err(HubError::validation(errors));
     }
     
     // Get home directory
-    let home = std::env::var("HOME").map_err(|e| format!("Failed to get HOME: {}", e))?;
+    let home = std::env::var("HOME").map_err(|e| HubError::not_found(format!("Failed to get HOME: {}", e)))?;
     let media_dir = format!("{}/Media/SpeechToText", home);
     
     // Create directory if it doesn't exist
     std::fs::create_dir_all(&media_dir)
-        .map_err(|e| format!("Failed to create directory: {}", e))?;
+        .map_err(|e| HubError::io("Failed to create directory", e))?;
     
     // Generate filename with timestamp
     let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
     let filename = format!("{}/recording_{}.wav", media_dir, timestamp);
     
-    // Start arecord process with high quality settings
-    // 48kHz, 16-bit, mono WAV format for best Whisper quality
-    let child = Command::new("arecord")
-        .args(["-f", "S16_LE", "-r", "48000", "-c", "1", &filename])
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .map_err(|e| format!("Failed to start arecord: {}", e))?;
-    
-    let pid = child.id();
+    // 16-bit mono WAV, at the rate the transcription engine wants
+    let source = InputSource::from_setting(stt_settings.input_device.as_deref());
+    let rate = stt_settings.engine.sample_rate();
+    let recorder = Recorder::start(source, Path::new(&filename), rate, move |level| {
+        let _ = app_handle.emit(recorder::RECORDING_LEVEL_EVENT, level);
+    })?;
     
     state.status = RecordingStatus::Recording;
-    state.process = Some(child);
-    state.pid = Some(pid);
+    state.recorder = Some(recorder);
     state.current_file = Some(filename);
     
     Ok(())
 }
 
 #[tauri::command]
-async fn pause_recording(recording: State<'_, RecordingRegistry>) -> Result<(), String> {
-    let mut state = recording.lock().map_err(|e| e.to_string())?;
+async fn pause_recording(recording: State<'_, RecordingRegistry>) -> Result<(), HubError> {
+    let mut state = recording.lock()?;
     
     if !matches!(state.status, RecordingStatus::Recording) {
-        return Err("Not currently recording".to_string());
+        return Err(HubError::invalid_state("Not currently recording"));
     }
     
-    if let Some(pid) = state.pid {
-        // Send SIGSTOP to pause the process
-        Command::new("kill")
-            .args(["-STOP", &pid.to_string()])
-            .output()
-            .map_err(|e| format!("Failed to pause recording: {}", e))?;
-        
+    if let Some(recorder) = &state.recorder {
+        recorder.pause();
         state.status = RecordingStatus::Paused;
         Ok(())
     } else {
-        Err("No recording process found".to_string())
+        Err(HubError::not_found("No recording process found"))
     }
 }
 
 #[tauri::command]
-async fn resume_recording(recording: State<'_, RecordingRegistry>) -> Result<(), String> {
-    let mut state = recording.lock().map_err(|e| e.to_string())?;
+async fn resume_recording(recording: State<'_, RecordingRegistry>) -> Result<(), HubError> {
+    let mut state = recording.lock()?;
     
     if !matches!(state.status, RecordingStatus::Paused) {
-        return Err("Recording is not paused".to_string());
+        return Err(HubError::invalid_state("Recording is not paused"));
     }
     
-    if let Some(pid) = state.pid {
-        // Send SIGCONT to resume the process
-        Command::new("kill")
-            .args(["-CONT", &pid.to_string()])
-            .output()
-            .map_err(|e| format!("Failed to resume recording: {}", e))?;
-        
+    if let Some(recorder) = &state.recorder {
+        recorder.resume();
         state.status = RecordingStatus::Recording;
         Ok(())
     } else {
-        Err("No recording process found".to_string())
+        Err(HubError::not_found("No recording process found"))
     }
 }
 
+// Stops recording and starts transcribing in the background. Returns the job
+// ID; the text arrives with the transcription-finished event.
 #[tauri::command]
-async fn stop_recording_and_transcribe(recording: State<'_, RecordingRegistry>) -> Result<String, String> {
-    let mut state = recording.lock().map_err(|e| e.to_string())?;
+async fn stop_recording_and_transcribe(
+    recording: State<'_, RecordingRegistry>,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, HubError> {
+    let mut state = recording.lock()?;
     
-    if matches!(state.status, RecordingStatus::Idle) {
-        return Err("No recording in progress".to_string());
+    if !matches!(state.status, RecordingStatus::Recording | RecordingStatus::Paused) {
+        return Err(HubError::invalid_state("No recording in progress"));
     }
     
-    // Kill the arecord process
-    if let Some(mut child) = state.process.take() {
-        child.kill().map_err(|e| format!("Failed to stop recording: {}", e))?;
-        child.wait().ok(); // Wait for process to fully terminate
+    if let Some(recorder) = state.recorder.take() {
+        if let Err(e) = recorder.stop() {
+            state.status = RecordingStatus::Idle;
+            state.current_file = None;
+            return Err(e);
+        }
     }
     
     let audio_file = state.current_file.clone()
-        .ok_or("No recording file found")?;
+        .ok_or_else(|| HubError::not_found("No recording file found"))?;
     
     state.status = RecordingStatus::Processing;
-    drop(state); // Release lock during long transcription
-    
-    // Get home directory for whisper path
-    let home = std::env::var("HOME").map_err(|e| format!("Failed to get HOME: {}", e))?;
-    let whisper_bin = format!("{}/.pyenv/versions/whisper-py312/bin/whisper", home);
-    
-    // Run Whisper transcription directly
-    // Model options: "small" (faster) or "medium" (better quality)
-    let model = "small";  // Change to "medium" for better quality
-    // let model = "medium";  // Uncomment to use medium model
-    let output = Command::new(&whisper_bin)
-        .args([&audio_file, "--model", model, "--device", "cuda", "--output_format", "txt", "--output_dir", "/tmp"])
-        .output()
-        .map_err(|e| format!("Failed to run Whisper: {}", e))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Whisper failed: {}", stderr));
-    }
-    
-    // Read the transcribed text file
-    let txt_file = audio_file.replace(".wav", ".txt");
-    let txt_path = format!("/tmp/{}", std::path::Path::new(&txt_file)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .ok_or("Invalid filename")?);
-    
-    let mut text = std::fs::read_to_string(&txt_path)
-        .map_err(|e| format!("Failed to read transcription: {}", e))?;
-    
-    // Remove timestamps like [00:00.000 --> 00:05.000]
-    let timestamp_regex = Regex::new(r"\[\d{2}:\d{2}\.\d{3} --> \d{2}:\d{2}\.\d{3}\]")
-        .map_err(|e| format!("Regex error: {}", e))?;
-    text = timestamp_regex.replace_all(&text, "").to_string();
-    
-    // Remove standalone date patterns (various formats)
-    let date_patterns = vec![
-        r"\d{1,2}/\d{1,2}/\d{2,4}",  // MM/DD/YYYY or DD/MM/YYYY
-        r"\d{4}-\d{2}-\d{2}",         // YYYY-MM-DD
-        r"\d{1,2}-\d{1,2}-\d{2,4}",  // MM-DD-YYYY or DD-MM-YYYY
-        r"(?i)(january|february|march|april|may|june|july|august|september|october|november|december)\s+\d{1,2},?\s+\d{4}",
-    ];
-    
-    for pattern in date_patterns {
-        let regex = Regex::new(pattern).map_err(|e| format!("Regex error: {}", e))?;
-        text = regex.replace_all(&text, "").to_string();
-    }
-    
-    // Clean up extra whitespace
-    text = text.trim().to_string();
-    let whitespace_regex = Regex::new(r"\s+").unwrap();
-    text = whitespace_regex.replace_all(&text, " ").to_string();
-    
-    // Copy to clipboard using xclip (X11) or wl-copy (Wayland)
+    drop(state);
+    
+    let stt_settings = settings.lock()?.stt.clone();
+    transcription::start(&app_handle, stt_settings, PathBuf::from(audio_file), |app_handle, result| {
+        // Keep the recording on disk if it failed, but allow a new one either way
+        if let Ok(mut state) = app_handle.state::<RecordingRegistry>().lock() {
+            state.status = RecordingStatus::Idle;
+            state.current_file = None;
+        }
+        let Ok(transcript) = result else { return };
+        // The transcript is already archived, so a missing clipboard tool only loses the copy
+        if let Err(e) = copy_to_clipboard(&transcript.text) {
+            eprintln!("Failed to copy transcript to the clipboard: {}", e);
+        }
+        
+        // Play notification sound (using system bell)
+        Command::new("paplay")
+            .arg("/usr/share/sounds/freedesktop/stereo/complete.oga")
+            .spawn()
+            .ok(); // Don't fail if sound doesn't play
+    })
+}
+
+// Copy to clipboard using xclip (X11) or wl-copy (Wayland)
+fn copy_to_clipboard(text: &str) -> Result<(), HubError> {
     let clipboard_cmd = if std::path::Path::new("/usr/bin/xclip").exists() {
         "xclip"
     } else if std::path::Path::new("/usr/bin/wl-copy").exists() {
         "wl-copy"
     } else {
-        return Err("No clipboard tool found (xclip or wl-copy required)".to_string());
+        return Err(HubError::CommandMissing {
+            message: "No clipboard tool found (xclip or wl-copy required)".to_string(),
+            command: "xclip".to_string(),
+        });
     };
     
     let mut cmd = Command::new(clipboard_cmd);
@@ -381,65 +703,146 @@ async fn stop_recording_and_transcribe(recording: State<'_, RecordingRegistry>)
             }
             child.wait()
         })
-        .map_err(|e| format!("Failed to copy to clipboard: {}", e))?;
+        .map_err(|e| HubError::io("Failed to copy to clipboard", e))?;
     
     if !clipboard_result.success() {
-        return Err("Failed to copy to clipboard".to_string());
+        return Err(HubError::CommandFailed {
+            message: "Failed to copy to clipboard".to_string(),
+            command: clipboard_cmd.to_string(),
+            exit_code: clipboard_result.code(),
+            stderr: String::new(),
+        });
     }
-    
-    // Play notification sound (using system bell)
-    Command::new("paplay")
-        .arg("/usr/share/sounds/freedesktop/stereo/complete.oga")
-        .spawn()
-        .ok(); // Don't fail if sound doesn't play
-    
-    // Reset state
-    let mut state = recording.lock().map_err(|e| e.to_string())?;
-    state.status = RecordingStatus::Idle;
-    state.pid = None;
-    state.current_file = None;
-    
-    Ok(text)
+    Ok(())
+}
+
+// Input devices recording can use; any of these names works as input_device
+#[tauri::command]
+async fn list_audio_devices() -> Result<Vec<AudioDevice>, HubError> {
+    tauri::async_runtime::spawn_blocking(recorder::list_devices)
+        .await
+        .map_err(|e| HubError::from(format!("Failed to list audio devices: {}", e)))?
+}
+
+#[tauri::command]
+async fn cancel_transcription(job_id: String, app_handle: tauri::AppHandle) -> Result<(), HubError> {
+    transcription::cancel(&app_handle, &job_id)
+}
+
+#[tauri::command]
+async fn list_transcripts(archive: State<'_, TranscriptArchive>) -> Result<Vec<TranscriptSummary>, HubError> {
+    let archive = archive.lock()?;
+    Ok(archive.list())
+}
+
+#[tauri::command]
+async fn search_transcripts(
+    query: String,
+    archive: State<'_, TranscriptArchive>,
+) -> Result<Vec<TranscriptSummary>, HubError> {
+    let archive = archive.lock()?;
+    Ok(archive.search(&query))
+}
+
+#[tauri::command]
+async fn get_transcript(id: String, archive: State<'_, TranscriptArchive>) -> Result<TranscriptRecord, HubError> {
+    let archive = archive.lock()?;
+    archive
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| HubError::not_found(format!("Transcript '{}' not found", id)))
+}
+
+// Removes the transcript from the archive. The recording stays on disk unless
+// `delete_audio` is set and no other transcript uses it.
+#[tauri::command]
+async fn delete_transcript(
+    id: String,
+    delete_audio: Option<bool>,
+    archive: State<'_, TranscriptArchive>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    let mut archive = archive.lock()?;
+    let orphaned_audio = archive.remove(&id)?;
+    transcripts::save_archive(&app_handle, &archive)?;
+    if let Some(audio) = orphaned_audio.filter(|_| delete_audio.unwrap_or(false)) {
+        transcripts::delete_audio(&audio)?;
+    }
+    Ok(())
+}
+
+// Transcribe an archived recording again, optionally with another engine or
+// model. The result is archived as a new transcript; returns the job ID.
+#[tauri::command]
+async fn retranscribe(
+    id: String,
+    engine: Option<SttEngine>,
+    model: Option<String>,
+    archive: State<'_, TranscriptArchive>,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, HubError> {
+    let audio = archive
+        .lock()?
+        .get(&id)
+        .ok_or_else(|| HubError::not_found(format!("Transcript '{}' not found", id)))?
+        .audio_path
+        .clone()
+        .filter(|path| path.is_file())
+        .ok_or_else(|| HubError::not_found(format!("The recording of transcript '{}' was deleted", id)))?;
+
+    let mut stt_settings = settings.lock()?.stt.clone();
+    if let Some(engine) = engine {
+        stt_settings.engine = engine;
+    }
+    if let Some(model) = model {
+        stt_settings.model = model;
+    }
+    // The archived recording stays, whatever the settings say
+    stt_settings.keep_audio = true;
+
+    let mut errors = stt::validate(&stt_settings);
+    errors.extend(stt::check_recording(stt_settings.engine, &audio)?);
+    if !errors.is_empty() {
+        return Err(HubError::validation(errors));
+    }
+    transcription::start(&app_handle, stt_settings, audio, |_, _| {})
 }
 
 // OSSEC commands
 
 #[tauri::command]
-async fn check_ossec_status() -> Result<bool, String> {
+async fn check_ossec_status() -> Result<bool, HubError> {
     // Check if OSSEC is running by looking for ossec processes
-    let output = Command::new("pgrep")
-        .arg("-f")
-        .arg("ossec")
-        .output()
-        .map_err(|e| format!("Failed to check OSSEC status: {}", e))?;
+    let output = error::output(
+        Command::new("pgrep")
+            .arg("-f")
+            .arg("ossec"),
+        "check OSSEC status",
+    )?;
     
     Ok(output.status.success() && !output.stdout.is_empty())
 }
 
 #[tauri::command]
-async fn toggle_ossec(start: bool) -> Result<(), String> {
+async fn toggle_ossec(start: bool) -> Result<(), HubError> {
     let action = if start { "start" } else { "stop" };
     
     // Use sh -c so the path resolution happens after privilege escalation
     let command_str = format!("/var/ossec/bin/ossec-control {}", action);
     
-    let output = Command::new("pkexec")
-        .arg("sh")
-        .arg("-c")
-        .arg(&command_str)
-        .output()
-        .map_err(|e| format!("Failed to {} OSSEC: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} OSSEC: {}", action, stderr))
-    }
+    error::run(
+        Command::new("pkexec")
+            .arg("sh")
+            .arg("-c")
+            .arg(&command_str),
+        &format!("{} OSSEC", action),
+    )?;
+    Ok(())
 }
 
 #[tauri::command]
-async fn open_file_in_terminal(file_path: String) -> Result<(), String> {
+async fn open_file_in_terminal(file_path: String) -> Result<(), HubError> {
     // Open file in nano using the user's default terminal emulator
     // Use pkexec for files that require elevated permissions
     let needs_sudo = file_path.starts_with("/var/ossec/") || 
@@ -474,25 +877,28 @@ async fn open_file_in_terminal(file_path: String) -> Result<(), String> {
             }
             
             cmd.spawn()
-                .map_err(|e| format!("Failed to open file: {}", e))?;
+                .map_err(|e| error::spawn_error(terminal, "open file", e))?;
             return Ok(());
         }
     }
     
-    Err("No supported terminal emulator found".to_string())
+    Err(HubError::CommandMissing {
+        message: "No supported terminal emulator found".to_string(),
+        command: "xterm".to_string(),
+    })
 }
 
 #[tauri::command]
-async fn check_alerts_log_modified(ossec_state: State<'_, OssecRegistry>) -> Result<bool, String> {
+async fn check_alerts_log_modified(ossec_state: State<'_, OssecRegistry>) -> Result<bool, HubError> {
     let log_path = "/var/ossec/logs/alerts/alerts.log";
     
     let metadata = fs::metadata(log_path)
-        .map_err(|e| format!("Failed to read log file metadata: {}", e))?;
+        .map_err(|e| HubError::io("Failed to read log file metadata", e))?;
     
     let current_mtime = metadata.modified()
-        .map_err(|e| format!("Failed to get modification time: {}", e))?;
+        .map_err(|e| HubError::io("Failed to get modification time", e))?;
     
-    let mut state = ossec_state.lock().map_err(|e| e.to_string())?;
+    let mut state = ossec_state.lock()?;
     
     if let Some(last_mtime) = state.alerts_log_mtime {
         let modified = current_mtime > last_mtime;
@@ -509,16 +915,16 @@ async fn check_alerts_log_modified(ossec_state: State<'_, OssecRegistry>) -> Res
 }
 
 #[tauri::command]
-async fn reset_alerts_log_baseline(ossec_state: State<'_, OssecRegistry>) -> Result<(), String> {
+async fn reset_alerts_log_baseline(ossec_state: State<'_, OssecRegistry>) -> Result<(), HubError> {
     let log_path = "/var/ossec/logs/alerts/alerts.log";
     
     let metadata = fs::metadata(log_path)
-        .map_err(|e| format!("Failed to read log file metadata: {}", e))?;
+        .map_err(|e| HubError::io("Failed to read log file metadata", e))?;
     
     let current_mtime = metadata.modified()
-        .map_err(|e| format!("Failed to get modification time: {}", e))?;
+        .map_err(|e| HubError::io("Failed to get modification time", e))?;
     
-    let mut state = ossec_state.lock().map_err(|e| e.to_string())?;
+    let mut state = ossec_state.lock()?;
     state.alerts_log_mtime = Some(current_mtime);
     
     // Also update file position to current end
@@ -535,8 +941,8 @@ async fn reset_alerts_log_baseline(ossec_state: State<'_, OssecRegistry>) -> Res
 async fn toggle_ossec_notifications(
     enabled: bool,
     ossec_state: State<'_, OssecRegistry>
-) -> Result<(), String> {
-    let mut state = ossec_state.lock().map_err(|e| e.to_string())?;
+) -> Result<(), HubError> {
+    let mut state = ossec_state.lock()?;
     state.notifications_enabled = enabled;
     Ok(())
 }
@@ -544,8 +950,8 @@ async fn toggle_ossec_notifications(
 #[tauri::command]
 async fn get_ossec_notifications_enabled(
     ossec_state: State<'_, OssecRegistry>
-) -> Result<bool, String> {
-    let state = ossec_state.lock().map_err(|e| e.to_string())?;
+) -> Result<bool, HubError> {
+    let state = ossec_state.lock()?;
     Ok(state.notifications_enabled)
 }
 
@@ -598,15 +1004,13 @@ fn check_for_new_alerts(app_handle: &AppHandle, ossec_state: &Arc<OssecRegistry>
     let mut new_alerts = Vec::new();
     let mut current_alert = String::new();
     
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            if line.starts_with("** Alert") && !current_alert.is_empty() {
-                new_alerts.push(current_alert.clone());
-                current_alert.clear();
-            }
-            current_alert.push_str(&line);
-            current_alert.push('\n');
+    for line in reader.lines().map_while(Result::ok) {
+        if line.starts_with("** Alert") && !current_alert.is_empty() {
+            new_alerts.push(current_alert.clone());
+            current_alert.clear();
         }
+        current_alert.push_str(&line);
+        current_alert.push('\n');
     }
     
     if !current_alert.is_empty() {
@@ -693,165 +1097,177 @@ fn start_alert_monitor(app_handle: AppHandle, ossec_state: Arc<OssecRegistry>) {
 // AIDE commands
 
 #[tauri::command]
-async fn aide_check() -> Result<String, String> {
-    let output = Command::new("pkexec")
-        .arg("/usr/bin/aide")
-        .arg("--check")
-        .output()
-        .map_err(|e| format!("Failed to run AIDE check: {}", e))?;
+async fn aide_check() -> Result<String, HubError> {
+    let mut cmd = Command::new("pkexec");
+    cmd.arg("/usr/bin/aide").arg("--check");
+    let output = error::output(&mut cmd, "run AIDE check")?;
+    let output = error::check_auth(&cmd, output, "run AIDE check")?;
     
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
     
-    // Check if pkexec was cancelled
-    if !output.status.success() && stdout.is_empty() && stderr.is_empty() {
-        return Err("Authentication cancelled or failed".to_string());
-    }
-    
     // AIDE returns non-zero exit code when changes are detected, which is not an error
     Ok(format!("{}{}", stdout, stderr))
 }
 
 #[tauri::command]
-async fn aide_update() -> Result<String, String> {
+async fn aide_update() -> Result<String, HubError> {
     // Run aide --update - note: AIDE returns non-zero when differences are found
     // So we need to check if the new database was created, not the exit code
-    let output = Command::new("pkexec")
-        .arg("sh")
+    let mut cmd = Command::new("pkexec");
+    cmd.arg("sh")
         .arg("-c")
-        .arg("/usr/bin/aide --update 2>&1; if [ -f /var/lib/aide/aide.db.new.gz ]; then mv /var/lib/aide/aide.db.new.gz /var/lib/aide/aide.db.gz 2>&1; echo 'Database moved successfully'; else echo 'Error: New database not created'; exit 1; fi")
-        .output()
-        .map_err(|e| format!("Failed to run AIDE update: {}", e))?;
+        .arg("/usr/bin/aide --update 2>&1; if [ -f /var/lib/aide/aide.db.new.gz ]; then mv /var/lib/aide/aide.db.new.gz /var/lib/aide/aide.db.gz 2>&1; echo 'Database moved successfully'; else echo 'Error: New database not created'; exit 1; fi");
+    let output = error::output(&mut cmd, "run AIDE update")?;
+    let output = error::check_auth(&cmd, output, "run AIDE update")?;
     
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
     
-    // Check if pkexec was cancelled
-    if !output.status.success() && stdout.is_empty() && stderr.is_empty() {
-        return Err("Authentication cancelled or failed".to_string());
-    }
-    
     // Check if database was successfully moved
     if stdout.contains("Database moved successfully") {
         Ok("AIDE database updated successfully".to_string())
     } else {
-        Err(format!("AIDE update failed: {}\n{}", stdout, stderr))
+        Err(HubError::CommandFailed {
+            message: format!("AIDE update failed: {}\n{}", stdout, stderr),
+            command: "aide".to_string(),
+            exit_code: output.status.code(),
+            stderr: format!("{}{}", stdout, stderr),
+        })
     }
 }
 
-// OpenSnitch commands
+// systemd service commands. Which units exist is configured in settings.json.
 
 #[tauri::command]
-async fn check_opensnitch_status() -> Result<bool, String> {
-    // Check if OpenSnitch is running by checking systemd service
-    let output = Command::new("systemctl")
-        .arg("is-active")
-        .arg("opensnitchd")
-        .output()
-        .map_err(|e| format!("Failed to check OpenSnitch status: {}", e))?;
-    
-    // systemctl is-active returns "active" and exit code 0 if running
-    Ok(output.status.success())
+async fn list_services(
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ServiceStatus>, HubError> {
+    let definitions = settings.lock()?.services.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let bus = app_handle.state::<SystemdBus>();
+        definitions.iter().map(|s| services::status(&bus, s)).collect()
+    })
+    .await
+    .map_err(|e| HubError::from(format!("Failed to list services: {}", e)))?
 }
 
 #[tauri::command]
-async fn toggle_opensnitch(start: bool) -> Result<(), String> {
-    let action = if start { "start" } else { "stop" };
-    
-    let output = Command::new("pkexec")
-        .arg("systemctl")
-        .arg(action)
-        .arg("opensnitchd")
-        .output()
-        .map_err(|e| format!("Failed to {} OpenSnitch: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} OpenSnitch: {}", action, stderr))
-    }
+async fn service_status(
+    id: String,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<ServiceStatus, HubError> {
+    let service = services::find(&settings.lock()?.services, &id)?.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        services::status(&app_handle.state::<SystemdBus>(), &service)
+    })
+    .await
+    .map_err(|e| HubError::from(format!("Failed to read service status: {}", e)))?
 }
 
-// Open WebUI commands
+// Start, stop, restart, enable or disable a configured service and return its
+// status afterwards
 #[tauri::command]
-async fn check_openwebui_status() -> Result<bool, String> {
-    // Check if Open WebUI is running by checking systemd service
-    let output = Command::new("systemctl")
-        .arg("is-active")
-        .arg("open-webui")
-        .output()
-        .map_err(|e| format!("Failed to check Open WebUI status: {}", e))?;
-    
-    // systemctl is-active returns "active" and exit code 0 if running
-    Ok(output.status.success())
+async fn service_action(
+    id: String,
+    action: ServiceAction,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<ServiceStatus, HubError> {
+    let service = services::find(&settings.lock()?.services, &id)?.clone();
+    // pkexec waits for the user to answer its prompt
+    tauri::async_runtime::spawn_blocking(move || {
+        services::run_action(&service, action)?;
+        services::status(&app_handle.state::<SystemdBus>(), &service)
+    })
+    .await
+    .map_err(|e| HubError::from(format!("Failed to {} service: {}", action.verb(), e)))?
 }
 
+// Recent journal entries of a configured unit, oldest first
 #[tauri::command]
-async fn toggle_openwebui(start: bool) -> Result<(), String> {
-    let action = if start { "start" } else { "stop" };
-    
-    let output = Command::new("pkexec")
-        .arg("systemctl")
-        .arg(action)
-        .arg("open-webui")
-        .output()
-        .map_err(|e| format!("Failed to {} Open WebUI: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} Open WebUI: {}", action, stderr))
-    }
+async fn get_service_journal(
+    unit: String,
+    lines: Option<u32>,
+    since: Option<String>,
+    priority: Option<u8>,
+    settings: State<'_, SettingsState>,
+) -> Result<Vec<JournalEntry>, HubError> {
+    let service = services::find_by_unit(&settings.lock()?.services, &unit)?.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        journal::read(&service, lines, since.as_deref(), priority)
+    })
+    .await
+    .map_err(|e| HubError::from(format!("Failed to read journal: {}", e)))?
+}
+
+// Stream new entries of a unit as "service-journal" events
+#[tauri::command]
+async fn follow_service_journal(
+    unit: String,
+    priority: Option<u8>,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    let service = services::find_by_unit(&settings.lock()?.services, &unit)?.clone();
+    journal::follow(&app_handle, &service, priority)
 }
 
-// check_lmstudio_status() -> Result<bool, String>
+#[tauri::command]
+async fn unfollow_service_journal(unit: String, app_handle: tauri::AppHandle) -> Result<(), HubError> {
+    journal::unfollow(&app_handle, &unit)
+}
+
+// check_lmstudio_status() -> Result<bool, HubError>
 // Checks if LM Studio process is running
 #[tauri::command]
-async fn check_lmstudio_status() -> Result<bool, String> {
+async fn check_lmstudio_status() -> Result<bool, HubError> {
     // Check if LM Studio is running by looking for its process
-    let output = Command::new("pgrep")
-        .arg("-f")
-        .arg("LM-Studio")
-        .output()
-        .map_err(|e| format!("Failed to check LM Studio status: {}", e))?;
+    let output = error::output(
+        Command::new("pgrep")
+            .arg("-f")
+            .arg("LM-Studio"),
+        "check LM Studio status",
+    )?;
     
     Ok(output.status.success() && !output.stdout.is_empty())
 }
 
-// toggle_lmstudio(start: bool) -> Result<(), String>
+// toggle_lmstudio(start: bool) -> Result<(), HubError>
 // Launches or stops LM Studio AppImage from ~/Prog directory
 #[tauri::command]
-async fn toggle_lmstudio(start: bool) -> Result<(), String> {
+async fn toggle_lmstudio(start: bool) -> Result<(), HubError> {
     if start {
         // Get home directory
         let home = std::env::var("HOME")
-            .map_err(|e| format!("Failed to get HOME directory: {}", e))?;
+            .map_err(|e| HubError::not_found(format!("Failed to get HOME directory: {}", e)))?;
         
         let prog_dir = format!("{}/Prog", home);
         let appimage_path = format!("{}/LM-Studio-0.3.32-2-x64.AppImage", prog_dir);
         
         // Check if AppImage exists
         if !std::path::Path::new(&appimage_path).exists() {
-            return Err(format!("LM Studio AppImage not found at: {}", appimage_path));
+            return Err(HubError::not_found(format!("LM Studio AppImage not found at: {}", appimage_path)));
         }
         
         // Launch the AppImage
         Command::new(&appimage_path)
             .current_dir(&prog_dir)
             .spawn()
-            .map_err(|e| format!("Failed to launch LM Studio: {}", e))?;
+            .map_err(|e| error::spawn_error(&appimage_path, "launch LM Studio", e))?;
         
         Ok(())
     } else {
         // To stop LM Studio, we need to find and kill its process
         // Look for the LM Studio process by name
-        let output = Command::new("pkill")
-            .arg("-f")
-            .arg("LM-Studio")
-            .output()
-            .map_err(|e| format!("Failed to stop LM Studio: {}", e))?;
+        let output = error::output(
+            Command::new("pkill")
+                .arg("-f")
+                .arg("LM-Studio"),
+            "stop LM Studio",
+        )?;
         
         if output.status.success() {
             Ok(())
@@ -862,87 +1278,54 @@ async fn toggle_lmstudio(start: bool) -> Result<(), String> {
     }
 }
 
-// check_ollama_status() -> Result<bool, String>
-// Checks if Ollama systemd service is active (running)
-#[tauri::command]
-async fn check_ollama_status() -> Result<bool, String> {
-    // Check if Ollama service is active
-    let output = Command::new("systemctl")
-        .arg("is-active")
-        .arg("ollama")
-        .output()
-        .map_err(|e| format!("Failed to check Ollama status: {}", e))?;
-    
-    Ok(output.status.success())
-}
-
-// toggle_ollama(start: bool) -> Result<(), String>
-// Starts or stops Ollama systemd service using pkexec
-#[tauri::command]
-async fn toggle_ollama(start: bool) -> Result<(), String> {
-    let action = if start { "start" } else { "stop" };
-    
-    let output = Command::new("pkexec")
-        .arg("systemctl")
-        .arg(action)
-        .arg("ollama")
-        .output()
-        .map_err(|e| format!("Failed to {} Ollama: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} Ollama: {}", action, stderr))
-    }
-}
-
-// check_warp_status() -> Result<bool, String>
+// check_warp_status() -> Result<bool, HubError>
 // Checks if Warp process is running
 #[tauri::command]
-async fn check_warp_status() -> Result<bool, String> {
+async fn check_warp_status() -> Result<bool, HubError> {
     // Check if warp is running by looking for its process
-    let output = Command::new("pgrep")
-        .arg("-f")
-        .arg("Warp")
-        .output()
-        .map_err(|e| format!("Failed to check Warp status: {}", e))?;
+    let output = error::output(
+        Command::new("pgrep")
+            .arg("-f")
+            .arg("Warp"),
+        "check Warp status",
+    )?;
     
     Ok(output.status.success() && !output.stdout.is_empty())
 }
 
-// toggle_warp(start: bool) -> Result<(), String>
+// toggle_warp(start: bool) -> Result<(), HubError>
 // Launches or stops Warp AppImage from ~/Prog directory
 #[tauri::command]
-async fn toggle_warp(start: bool) -> Result<(), String> {
+async fn toggle_warp(start: bool) -> Result<(), HubError> {
     if start {
         // Get home directory
         let home = std::env::var("HOME")
-            .map_err(|e| format!("Failed to get HOME directory: {}", e))?;
+            .map_err(|e| HubError::not_found(format!("Failed to get HOME directory: {}", e)))?;
         
         let prog_dir = format!("{}/Prog", home);
         let appimage_path = format!("{}/Warp-x86_64.AppImage", prog_dir);
         
         // Check if AppImage exists
         if !std::path::Path::new(&appimage_path).exists() {
-            return Err(format!("Warp AppImage not found at: {}", appimage_path));
+            return Err(HubError::not_found(format!("Warp AppImage not found at: {}", appimage_path)));
         }
         
         // Launch the AppImage
         Command::new(&appimage_path)
             .current_dir(&prog_dir)
             .spawn()
-            .map_err(|e| format!("Failed to launch Warp AI: {}", e))?;
+            .map_err(|e| error::spawn_error(&appimage_path, "launch Warp AI", e))?;
         
         Ok(())
     } else {
         // To stop Warp, we need to find and kill its process
         // Look for the Warp process by name
-        let output = Command::new("pkill")
-            .arg("-f")
-            .arg("Warp")
-            .output()
-            .map_err(|e| format!("Failed to stop Warp: {}", e))?;
+        let output = error::output(
+            Command::new("pkill")
+                .arg("-f")
+                .arg("Warp"),
+            "stop Warp",
+        )?;
         
         if output.status.success() {
             Ok(())
@@ -953,79 +1336,14 @@ async fn toggle_warp(start: bool) -> Result<(), String> {
     }
 }
 
-// Docker commands
-#[tauri::command]
-async fn check_docker_enabled() -> Result<bool, String> {
-    // Check if Docker service is enabled
-    let output = Command::new("systemctl")
-        .arg("is-enabled")
-        .arg("docker")
-        .output()
-        .map_err(|e| format!("Failed to check Docker enabled status: {}", e))?;
-    
-    // systemctl is-enabled returns "enabled" and exit code 0 if enabled
-    Ok(output.status.success())
-}
-
-#[tauri::command]
-async fn check_docker_active() -> Result<bool, String> {
-    // Check if Docker service is active (running)
-    let output = Command::new("systemctl")
-        .arg("is-active")
-        .arg("docker")
-        .output()
-        .map_err(|e| format!("Failed to check Docker active status: {}", e))?;
-    
-    // systemctl is-active returns "active" and exit code 0 if running
-    Ok(output.status.success())
-}
-
-#[tauri::command]
-async fn toggle_docker_enable(enable: bool) -> Result<(), String> {
-    let action = if enable { "enable" } else { "disable" };
-    
-    let output = Command::new("pkexec")
-        .arg("systemctl")
-        .arg(action)
-        .arg("docker")
-        .output()
-        .map_err(|e| format!("Failed to {} Docker: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} Docker: {}", action, stderr))
-    }
-}
-
-#[tauri::command]
-async fn toggle_docker_active(start: bool) -> Result<(), String> {
-    let action = if start { "start" } else { "stop" };
-    
-    let output = Command::new("pkexec")
-        .arg("systemctl")
-        .arg(action)
-        .arg("docker")
-        .output()
-        .map_err(|e| format!("Failed to {} Docker: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} Docker: {}", action, stderr))
-    }
-}
-
 // System monitoring commands
 
 #[tauri::command]
-fn get_ram_usage() -> Result<(f64, f64, f64), String> {
+fn get_ram_usage() -> Result<(f64, f64, f64), HubError> {
     // Returns (used_gb, total_gb, percent)
     // Read directly from /proc/meminfo - fastest method
     let meminfo = std::fs::read_to_string("/proc/meminfo")
-        .map_err(|e| format!("Failed to read meminfo: {}", e))?;
+        .map_err(|e| HubError::io("Failed to read meminfo", e))?;
     
     let mut mem_total = 0u64;
     let mut mem_available = 0u64;
@@ -1050,102 +1368,85 @@ fn get_ram_usage() -> Result<(f64, f64, f64), String> {
     Ok((used_gb, total_gb, percent))
 }
 
+// CPU, load, memory, swap, disks, network and temperatures in one go. Rates
+// are measured since the previous call.
 #[tauri::command]
-fn get_gpu_usage() -> Result<(f64, f64, f64), String> {
-    // Returns (used_gb, total_gb, percent)
-    // Use nvidia-smi to get GPU memory usage
-    let output = Command::new("nvidia-smi")
-        .arg("--query-gpu=memory.used,memory.total")
-        .arg("--format=csv,noheader,nounits")
-        .output()
-        .map_err(|e| format!("Failed to get GPU usage: {}", e))?;
-    
-    if !output.status.success() {
-        return Err("nvidia-smi command failed. Is NVIDIA GPU available?".to_string());
-    }
-    
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let line = stdout.lines().next().ok_or("No GPU data found")?;
-    let parts: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
-    
-    if parts.len() >= 2 {
-        let used_mb: f64 = parts[0].parse().map_err(|_| "Failed to parse used memory")?;
-        let total_mb: f64 = parts[1].parse().map_err(|_| "Failed to parse total memory")?;
-        
-        let used_gb = used_mb / 1024.0;
-        let total_gb = total_mb / 1024.0;
-        let percent = if total_mb > 0.0 { (used_mb / total_mb) * 100.0 } else { 0.0 };
-        
-        Ok((used_gb, total_gb, percent))
-    } else {
-        Err("Invalid GPU data format".to_string())
-    }
+async fn get_system_snapshot(app_handle: tauri::AppHandle) -> Result<SystemSnapshot, HubError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<SystemMonitorRegistry>();
+        let mut state = state.lock()?;
+        Ok(sysmon::snapshot(&mut state))
+    })
+    .await
+    .map_err(|e| HubError::from(format!("Failed to read system snapshot: {}", e)))?
 }
 
-// Docker Desktop commands (user service)
+#[tauri::command]
+async fn get_alert_rules(settings: State<'_, SettingsState>) -> Result<Vec<AlertRule>, HubError> {
+    let settings = settings.lock()?;
+    Ok(settings.alert_rules.clone())
+}
 
 #[tauri::command]
-async fn check_docker_desktop_enabled() -> Result<bool, String> {
-    // Check if Docker Desktop user service is enabled
-    let output = Command::new("systemctl")
-        .arg("--user")
-        .arg("is-enabled")
-        .arg("docker-desktop.service")
-        .output()
-        .map_err(|e| format!("Failed to check Docker Desktop enabled status: {}", e))?;
-    
-    Ok(output.status.success())
+async fn set_alert_rules(
+    rules: Vec<AlertRule>,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    let errors = alerts::validate_rules(&rules);
+    if !errors.is_empty() {
+        return Err(HubError::validation(errors));
+    }
+    let mut settings = settings.lock()?;
+    settings.alert_rules = rules;
+    settings::save_settings(&app_handle, &settings)
 }
 
 #[tauri::command]
-async fn check_docker_desktop_active() -> Result<bool, String> {
-    // Check if Docker Desktop user service is active (running)
-    let output = Command::new("systemctl")
-        .arg("--user")
-        .arg("is-active")
-        .arg("docker-desktop.service")
-        .output()
-        .map_err(|e| format!("Failed to check Docker Desktop active status: {}", e))?;
-    
-    Ok(output.status.success())
+async fn get_stt_settings(settings: State<'_, SettingsState>) -> Result<SttSettings, HubError> {
+    let settings = settings.lock()?;
+    Ok(settings.stt.clone())
 }
 
 #[tauri::command]
-async fn toggle_docker_desktop_enable(enable: bool) -> Result<(), String> {
-    let action = if enable { "enable" } else { "disable" };
-    
-    let output = Command::new("systemctl")
-        .arg("--user")
-        .arg(action)
-        .arg("docker-desktop.service")
-        .output()
-        .map_err(|e| format!("Failed to {} Docker Desktop: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} Docker Desktop: {}", action, stderr))
+async fn set_stt_settings(
+    stt_settings: SttSettings,
+    settings: State<'_, SettingsState>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), HubError> {
+    let errors = stt::validate(&stt_settings);
+    if !errors.is_empty() {
+        return Err(HubError::validation(errors));
     }
+    let mut settings = settings.lock()?;
+    settings.stt = stt_settings;
+    settings::save_settings(&app_handle, &settings)
 }
 
+// Problems with the current speech-to-text settings, empty when recording can start
 #[tauri::command]
-async fn toggle_docker_desktop_active(start: bool) -> Result<(), String> {
-    let action = if start { "start" } else { "stop" };
-    
-    let output = Command::new("systemctl")
-        .arg("--user")
-        .arg(action)
-        .arg("docker-desktop.service")
-        .output()
-        .map_err(|e| format!("Failed to {} Docker Desktop: {}", action, e))?;
-    
-    if output.status.success() {
-        Ok(())
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Failed to {} Docker Desktop: {}", action, stderr))
-    }
+async fn check_stt_settings(settings: State<'_, SettingsState>) -> Result<Vec<FieldError>, HubError> {
+    let settings = settings.lock()?;
+    Ok(stt::validate(&settings.stt))
+}
+
+// Polled by the UI, so it runs off the main thread
+#[tauri::command]
+async fn get_gpu_usage(app_handle: tauri::AppHandle) -> Result<(f64, f64, f64), HubError> {
+    // Returns (used_gb, total_gb, percent)
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<GpuProviders>().vram_usage())
+        .await
+        .map_err(|e| HubError::from(format!("Failed to read GPU usage: {}", e)))?
+        .map_err(HubError::not_found)
+}
+
+// Every GPU any provider can see, with utilization, VRAM, temperature and the
+// processes using it
+#[tauri::command]
+async fn get_gpus(app_handle: tauri::AppHandle) -> Result<Vec<GpuInfo>, HubError> {
+    tauri::async_runtime::spawn_blocking(move || app_handle.state::<GpuProviders>().all_gpus())
+        .await
+        .map_err(|e| HubError::from(format!("Failed to read GPUs: {}", e)))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -1156,14 +1457,30 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             get_registered_apps,
             register_app,
+            update_app,
+            discover_apps,
+            register_discovered_apps,
+            get_discovery_roots,
+            set_discovery_roots,
             launch_app,
             stop_app,
             remove_app,
+            get_app_logs,
+            get_app_metrics,
+            query_metrics,
+            list_metrics,
             get_recording_status,
             start_recording,
             pause_recording,
             resume_recording,
             stop_recording_and_transcribe,
+            list_audio_devices,
+            cancel_transcription,
+            list_transcripts,
+            search_transcripts,
+            get_transcript,
+            delete_transcript,
+            retranscribe,
             check_ossec_status,
             toggle_ossec,
             open_file_in_terminal,
@@ -1173,43 +1490,131 @@ pub fn run() {
             get_ossec_notifications_enabled,
             aide_check,
             aide_update,
-            check_opensnitch_status,
-            toggle_opensnitch,
-            check_openwebui_status,
-            toggle_openwebui,
+            list_services,
+            service_status,
+            service_action,
+            get_service_journal,
+            follow_service_journal,
+            unfollow_service_journal,
             check_lmstudio_status,
             toggle_lmstudio,
-            check_ollama_status,
-            toggle_ollama,
             check_warp_status,
             toggle_warp,
-            check_docker_enabled,
-            check_docker_active,
-            toggle_docker_enable,
-            toggle_docker_active,
-            check_docker_desktop_enabled,
-            check_docker_desktop_active,
-            toggle_docker_desktop_enable,
-            toggle_docker_desktop_active,
             get_ram_usage,
-            get_gpu_usage
+            get_system_snapshot,
+            get_alert_rules,
+            set_alert_rules,
+            get_stt_settings,
+            set_stt_settings,
+            check_stt_settings,
+            get_gpu_usage,
+            get_gpus
         ])
         .setup(|app| {
             // Load registry from disk
-            let registry_data = load_registry(&app.handle())
-                .unwrap_or_else(|e| {
+            let mut registry_data = match load_registry(app.handle()) {
+                Ok(loaded) => {
+                    if let Some(backup) = &loaded.recovered_from {
+                        let _ = app.notification()
+                            .builder()
+                            .title("⚠️ Tauri Hub registry restored")
+                            .body(format!("registry.json was unreadable, loaded {:?} instead.", backup))
+                            .show();
+                    }
+                    loaded.apps
+                }
+                Err(e) => {
                     eprintln!("Failed to load registry: {}", e);
+                    // Keep the unreadable file instead of overwriting it on the next save
+                    match registry::get_registry_path(app.handle())
+                        .and_then(|path| registry::set_aside_unreadable(&path))
+                    {
+                        Ok(Some(path)) => eprintln!("Moved unreadable registry to {:?}", path),
+                        Ok(None) => {}
+                        Err(e) => eprintln!("{}", e),
+                    }
+                    let _ = app.notification()
+                        .builder()
+                        .title("⚠️ Tauri Hub registry unreadable")
+                        .body("No usable registry or backup was found, starting with no apps.")
+                        .show();
                     HashMap::new()
-                });
+                }
+            };
+            
+            // Apps persisted as Running may have died with the previous session
+            let adopted = supervisor::reconcile_running_apps(&mut registry_data);
+            if let Err(e) = save_registry_status(app.handle(), &registry_data) {
+                eprintln!("Failed to save reconciled registry: {}", e);
+            }
             
             let app_registry: AppRegistry = Mutex::new(registry_data);
             app.manage(app_registry);
             
+            let process_registry: ProcessRegistry = Mutex::new(adopted);
+            app.manage(process_registry);
+            
+            let restart_registry: RestartRegistry = Mutex::new(HashMap::new());
+            app.manage(restart_registry);
+            
+            let log_registry: LogRegistry = Mutex::new(HashMap::new());
+            app.manage(log_registry);
+            
+            let settings = settings::load_settings(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Failed to load settings, using defaults: {}", e);
+                settings::HubSettings::default()
+            });
+            let settings_state: SettingsState = Mutex::new(settings);
+            app.manage(settings_state);
+            
+            let metrics_registry: MetricsRegistry = Mutex::new(Default::default());
+            app.manage(metrics_registry);
+            
+            let system_monitor: SystemMonitorRegistry = Mutex::new(Default::default());
+            app.manage(system_monitor);
+            
+            app.manage(gpu::detect_providers());
+            
+            // Service cards update from systemd's PropertiesChanged signals
+            app.manage(SystemdBus::default());
+            systemd::start_service_watcher(app.handle());
+            
+            let journal_followers: JournalFollowers = Mutex::new(HashMap::new());
+            app.manage(journal_followers);
+            
+            let transcription_jobs: TranscriptionJobs = Mutex::new(HashMap::new());
+            app.manage(transcription_jobs);
+            
+            let transcript_index = transcripts::load_archive(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Starting with an empty transcript archive: {}", e);
+                Default::default()
+            });
+            let transcript_archive: TranscriptArchive = Mutex::new(transcript_index);
+            app.manage(transcript_archive);
+            
+            let history = history::load_history(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Starting with empty metrics history: {}", e);
+                Default::default()
+            });
+            let history_registry: HistoryRegistry = Mutex::new(history);
+            app.manage(history_registry);
+            history::start_history_sampler(app.handle().clone());
+            
+            // Desktop notifications when resources cross the configured thresholds
+            alerts::start_alert_watcher(app.handle().clone());
+            
+            // Keep registered apps in sync with their projects on disk
+            if let Err(e) = watcher::start_app_watcher(app.handle()) {
+                eprintln!("{}", e);
+            }
+            
+            // Watch launched apps so crashes show up without polling
+            supervisor::start_process_supervisor(app.handle().clone());
+            
             // Initialize recording state
             let recording_state = RecordingRegistry::new(RecordingState {
                 status: RecordingStatus::Idle,
-                process: None,
-                pid: None,
+                recorder: None,
                 current_file: None,
             });
             app.manage(recording_state);
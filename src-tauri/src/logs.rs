@@ -0,0 +1,259 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::HubError;
+use crate::get_app_data_dir;
+use crate::process::ManagedProcess;
+
+// Recent lines kept in memory per app for get_app_logs
+const RING_BUFFER_LINES: usize = 2000;
+
+// Rotate the log file once it grows past this size
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+// Number of rotated files kept as <app_id>.log.1 .. <app_id>.log.N
+const ROTATED_LOG_FILES: usize = 3;
+
+pub const APP_LOG_EVENT: &str = "app-log";
+
+#[derive(Debug, Clone, Serialize)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    // Increases monotonically per app, so the frontend can ask for everything
+    // after the last line it has seen
+    pub seq: u64,
+    pub timestamp: String,
+    pub stream: LogStream,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppLogEvent {
+    pub app_id: String,
+    pub line: LogLine,
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { path, file, size })
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..ROTATED_LOG_FILES).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &LogLine) -> io::Result<()> {
+        if self.size >= MAX_LOG_FILE_BYTES {
+            self.rotate()?;
+        }
+
+        let stream = match line.stream {
+            LogStream::Stdout => "out",
+            LogStream::Stderr => "err",
+        };
+        let entry = format!("{} [{}] {}\n", line.timestamp, stream, line.text);
+        self.file.write_all(entry.as_bytes())?;
+        self.size += entry.len() as u64;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct AppLog {
+    lines: VecDeque<LogLine>,
+    next_seq: u64,
+    file: Option<RotatingFile>,
+}
+
+impl AppLog {
+    fn push(&mut self, stream: LogStream, text: String) -> LogLine {
+        self.next_seq += 1;
+        let line = LogLine {
+            seq: self.next_seq,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            stream,
+            text,
+        };
+
+        if let Some(file) = self.file.as_mut() {
+            if let Err(e) = file.write_line(&line) {
+                eprintln!("Failed to write log file {:?}: {}", file.path, e);
+            }
+        }
+
+        if self.lines.len() == RING_BUFFER_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line.clone());
+        line
+    }
+
+    pub fn since(&self, seq: Option<u64>) -> Vec<LogLine> {
+        let after = seq.unwrap_or(0);
+        self.lines.iter().filter(|l| l.seq > after).cloned().collect()
+    }
+}
+
+pub type LogRegistry = Mutex<HashMap<String, AppLog>>;
+
+pub fn get_log_path(app_handle: &AppHandle, app_id: &str) -> Result<PathBuf, HubError> {
+    // Apps registered before ids were validated must not escape the log dir
+    if app_id.is_empty() || app_id.contains(['/', '\\']) {
+        return Err(HubError::from(format!("Invalid app id '{}' for a log file", app_id)));
+    }
+    let log_dir = get_app_data_dir(app_handle)?.join("logs");
+    fs::create_dir_all(&log_dir)
+        .map_err(|e| HubError::io("Failed to create log dir", e))?;
+    Ok(log_dir.join(format!("{}.log", app_id)))
+}
+
+fn pump<R: Read>(app_handle: AppHandle, app_id: String, stream: LogStream, reader: R) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Failed to read output of app '{}': {}", app_id, e);
+                break;
+            }
+        }
+
+        // Apps don't always print valid UTF-8, don't let that end the capture
+        let text = String::from_utf8_lossy(&buf)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+
+        let line = {
+            let logs = app_handle.state::<LogRegistry>();
+            let mut logs = match logs.lock() {
+                Ok(l) => l,
+                Err(_) => break,
+            };
+            logs.entry(app_id.clone())
+                .or_default()
+                .push(stream.clone(), text)
+        };
+
+        let event = AppLogEvent {
+            app_id: app_id.clone(),
+            line,
+        };
+        let _ = app_handle.emit(APP_LOG_EVENT, event);
+    }
+}
+
+// Take the piped stdout/stderr of a freshly spawned app and copy every line into
+// its ring buffer and log file on background threads
+pub fn capture_output(app_handle: &AppHandle, app_id: &str, process: &mut ManagedProcess) {
+    let child = match process.child.as_mut() {
+        Some(child) => child,
+        None => return,
+    };
+
+    match get_log_path(app_handle, app_id).and_then(|path| {
+        RotatingFile::open(path).map_err(|e| HubError::io("Failed to open log file", e))
+    }) {
+        Ok(file) => {
+            if let Ok(mut logs) = app_handle.state::<LogRegistry>().lock() {
+                logs.entry(app_id.to_string()).or_default().file = Some(file);
+            }
+        }
+        Err(e) => eprintln!("Logging app '{}' to memory only: {}", app_id, e),
+    }
+
+    if let Some(stdout) = child.stdout.take() {
+        let (handle, id) = (app_handle.clone(), app_id.to_string());
+        thread::spawn(move || pump(handle, id, LogStream::Stdout, stdout));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        let (handle, id) = (app_handle.clone(), app_id.to_string());
+        thread::spawn(move || pump(handle, id, LogStream::Stderr, stderr));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    #[test]
+    fn keeps_only_the_newest_lines_in_memory() {
+        let mut log = AppLog::default();
+        for n in 0..RING_BUFFER_LINES + 5 {
+            log.push(LogStream::Stdout, format!("line {}", n));
+        }
+
+        let lines = log.since(None);
+        assert_eq!(lines.len(), RING_BUFFER_LINES);
+        assert_eq!(lines[0].seq, 6);
+        assert_eq!(lines[0].text, "line 5");
+
+        let tail = log.since(Some(RING_BUFFER_LINES as u64 + 2));
+        assert_eq!(tail.iter().map(|l| l.seq).collect::<Vec<_>>(), vec![2003, 2004, 2005]);
+    }
+
+    #[test]
+    fn rotates_full_log_files() {
+        let fx = Fixture::new("logs-rotate");
+        let path = fx.dir.join("app.log");
+        let mut log = AppLog { file: Some(RotatingFile::open(path.clone()).unwrap()), ..AppLog::default() };
+
+        for n in 0..ROTATED_LOG_FILES + 2 {
+            log.push(LogStream::Stderr, format!("generation {}", n));
+            // Pretend the file filled up, so the next line starts a new one
+            log.file.as_mut().unwrap().size = MAX_LOG_FILE_BYTES;
+        }
+
+        let read = |path: &PathBuf| fs::read_to_string(path).unwrap();
+        let file = log.file.as_ref().unwrap();
+        assert!(read(&path).ends_with("[err] generation 4\n"));
+        assert!(read(&file.rotated_path(1)).ends_with("generation 3\n"));
+        assert!(read(&file.rotated_path(ROTATED_LOG_FILES)).ends_with("generation 1\n"));
+        assert!(!file.rotated_path(ROTATED_LOG_FILES + 1).exists());
+
+        // Appending to an existing file picks up its size
+        let reopened = RotatingFile::open(path.clone()).unwrap();
+        assert_eq!(reopened.size, read(&path).len() as u64);
+    }
+}
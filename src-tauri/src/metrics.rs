@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::error::HubError;
+use crate::process::{self, ProcStat};
+use crate::{AppStatus, TauriApp};
+
+// When an app has no earlier CPU sample we take a second one this much later,
+// so the first reading isn't empty
+const FIRST_SAMPLE_GAP: Duration = Duration::from_millis(200);
+
+// Resource usage of an app's whole process tree (the launched process and
+// everything below it)
+#[derive(Debug, Clone, Serialize)]
+pub struct AppMetrics {
+    // Percent of one core, so busy multi-threaded apps can go above 100
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    pub threads: u64,
+    pub open_fds: u64,
+    pub uptime_secs: u64,
+    pub processes: usize,
+    pub sampled_at: String,
+}
+
+// CPU time of an app's tree at the last sample, to compute usage from
+struct CpuSample {
+    pid: u32,
+    start_time: u64,
+    ticks: u64,
+    at: Instant,
+}
+
+#[derive(Default)]
+pub struct MetricsState {
+    cpu: HashMap<String, CpuSample>,
+}
+
+pub type MetricsRegistry = Mutex<MetricsState>;
+
+fn clock_ticks_per_sec() -> f64 {
+    // SAFETY: sysconf has no preconditions
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 {
+        ticks as f64
+    } else {
+        100.0
+    }
+}
+
+fn system_uptime_secs() -> Option<f64> {
+    fs::read_to_string("/proc/uptime")
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+// Snapshot of /proc/<pid>/stat for every process on the system
+fn read_process_table() -> HashMap<u32, ProcStat> {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return HashMap::new(),
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_string_lossy().parse::<u32>().ok())
+        .filter_map(|pid| process::read_proc_stat(pid).map(|stat| (pid, stat)))
+        .collect()
+}
+
+fn process_tree(root: u32, table: &HashMap<u32, ProcStat>) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, stat) in table {
+        children.entry(stat.ppid).or_default().push(*pid);
+    }
+
+    let mut tree = vec![root];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(kids) = children.get(&tree[i]) {
+            tree.extend(kids);
+        }
+        i += 1;
+    }
+    tree
+}
+
+// VmRSS (in bytes) and Threads from the contents of /proc/<pid>/status
+fn parse_status(status: &str) -> (u64, u64) {
+    let mut rss = 0;
+    let mut threads = 0;
+    for line in status.lines() {
+        let value = || {
+            line.split_whitespace()
+                .nth(1)
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        if line.starts_with("VmRSS:") {
+            rss = value() * 1024;
+        } else if line.starts_with("Threads:") {
+            threads = value();
+        }
+    }
+    (rss, threads)
+}
+
+fn read_status(pid: u32) -> (u64, u64) {
+    parse_status(&fs::read_to_string(format!("/proc/{}/status", pid)).unwrap_or_default())
+}
+
+// Other users' processes in the tree (e.g. after a setuid helper) can't be
+// inspected and count as zero
+fn count_fds(pid: u32) -> u64 {
+    fs::read_dir(format!("/proc/{}/fd", pid))
+        .map(|entries| entries.count() as u64)
+        .unwrap_or(0)
+}
+
+struct TreeUsage {
+    root: ProcStat,
+    ticks: u64,
+    rss_bytes: u64,
+    threads: u64,
+    open_fds: u64,
+    processes: usize,
+}
+
+// The app's process tree plus, for a group leader, everything else in its
+// process group: daemonized helpers get reparented away from the tree but
+// stay in the group the app was launched in
+fn app_pids(pid: u32, table: &HashMap<u32, ProcStat>) -> Vec<u32> {
+    let mut tree = process_tree(pid, table);
+    if let Some(root) = table.get(&pid).filter(|root| root.pgid == pid as i32) {
+        let mut in_group: Vec<u32> = table
+            .iter()
+            .filter(|(p, stat)| stat.pgid == root.pgid && !tree.contains(p))
+            .map(|(p, _)| *p)
+            .collect();
+        in_group.sort();
+        tree.append(&mut in_group);
+    }
+    tree
+}
+
+fn tree_usage(pid: u32, table: &HashMap<u32, ProcStat>) -> Option<TreeUsage> {
+    let root = table.get(&pid)?.clone();
+    let tree = app_pids(pid, table);
+
+    let mut usage = TreeUsage {
+        root,
+        ticks: 0,
+        rss_bytes: 0,
+        threads: 0,
+        open_fds: 0,
+        processes: tree.len(),
+    };
+    for pid in tree {
+        let (rss, threads) = read_status(pid);
+        usage.ticks += table.get(&pid).map(|s| s.cpu_ticks).unwrap_or(0);
+        usage.rss_bytes += rss;
+        usage.threads += threads;
+        usage.open_fds += count_fds(pid);
+    }
+    Some(usage)
+}
+
+fn running_pid(app: &TauriApp) -> Option<u32> {
+    match app.status {
+        AppStatus::Running => app.pid,
+        _ => None,
+    }
+}
+
+// Sample every running app in `apps` with a single pass over /proc. Apps that
+// aren't running (or whose process vanished) are left out. Without
+// `wait_for_baseline`, apps sampled for the first time report 0% CPU instead
+// of blocking for FIRST_SAMPLE_GAP.
+pub fn sample_apps<'a>(
+    registry: &MetricsRegistry,
+    apps: impl IntoIterator<Item = &'a TauriApp>,
+    wait_for_baseline: bool,
+) -> Result<HashMap<String, AppMetrics>, HubError> {
+    let running: Vec<(String, u32)> = apps
+        .into_iter()
+        .filter_map(|app| running_pid(app).map(|pid| (app.id.clone(), pid)))
+        .collect();
+    if running.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut state = registry.lock()?;
+    let has_baseline = |id: &str, pid: u32| state.cpu.get(id).map(|s| s.pid == pid).unwrap_or(false);
+    if wait_for_baseline && !running.iter().all(|(id, pid)| has_baseline(id, *pid)) {
+        let table = read_process_table();
+        let now = Instant::now();
+        for (id, pid) in &running {
+            if let Some(usage) = tree_usage(*pid, &table) {
+                state.cpu.insert(
+                    id.clone(),
+                    CpuSample {
+                        pid: *pid,
+                        start_time: usage.root.start_time,
+                        ticks: usage.ticks,
+                        at: now,
+                    },
+                );
+            }
+        }
+        // Other readers get the lock back while we wait for the second sample
+        drop(state);
+        thread::sleep(FIRST_SAMPLE_GAP);
+        state = registry.lock()?;
+    }
+
+    let table = read_process_table();
+    let now = Instant::now();
+    let clock = clock_ticks_per_sec();
+    let uptime = system_uptime_secs().unwrap_or(0.0);
+    let sampled_at = chrono::Local::now().to_rfc3339();
+
+    let mut metrics = HashMap::new();
+    for (id, pid) in running {
+        let usage = match tree_usage(pid, &table) {
+            Some(usage) => usage,
+            None => {
+                state.cpu.remove(&id);
+                continue;
+            }
+        };
+
+        // Children exiting take their CPU time with them, so the sum can
+        // shrink; treat that as idle rather than going negative
+        let cpu_percent = match state.cpu.get(&id) {
+            Some(prev) if prev.pid == pid && prev.start_time == usage.root.start_time => {
+                let elapsed = now.duration_since(prev.at).as_secs_f64();
+                if elapsed > 0.0 {
+                    usage.ticks.saturating_sub(prev.ticks) as f64 / clock / elapsed * 100.0
+                } else {
+                    0.0
+                }
+            }
+            _ => 0.0,
+        };
+
+        state.cpu.insert(
+            id.clone(),
+            CpuSample {
+                pid,
+                start_time: usage.root.start_time,
+                ticks: usage.ticks,
+                at: now,
+            },
+        );
+
+        let started_secs = usage.root.start_time as f64 / clock;
+        metrics.insert(
+            id,
+            AppMetrics {
+                cpu_percent,
+                rss_bytes: usage.rss_bytes,
+                threads: usage.threads,
+                open_fds: usage.open_fds,
+                uptime_secs: (uptime - started_secs).max(0.0) as u64,
+                processes: usage.processes,
+                sampled_at: sampled_at.clone(),
+            },
+        );
+    }
+
+    Ok(metrics)
+}
+
+// sample_apps off the async runtime, it sleeps and reads a lot of small files
+pub async fn sample(
+    app_handle: &AppHandle,
+    apps: Vec<TauriApp>,
+    wait_for_baseline: bool,
+) -> Result<HashMap<String, AppMetrics>, HubError> {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        sample_apps(&app_handle.state::<MetricsRegistry>(), &apps, wait_for_baseline)
+    })
+    .await
+    .map_err(|e| HubError::from(format!("Failed to sample metrics: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(ppid: u32, pgid: i32) -> ProcStat {
+        ProcStat { ppid, pgid, cpu_ticks: 0, start_time: 0 }
+    }
+
+    #[test]
+    fn collects_process_tree_and_group() {
+        let table = HashMap::from([
+            (1, stat(0, 1)),
+            (100, stat(1, 100)),
+            (101, stat(100, 100)),
+            (102, stat(101, 100)),
+            // Daemonized helper, reparented to init but still in the group
+            (150, stat(1, 100)),
+            // Started by the app in a group of its own
+            (160, stat(100, 160)),
+            (200, stat(1, 200)),
+        ]);
+
+        let mut tree = process_tree(100, &table);
+        tree.sort();
+        assert_eq!(tree, vec![100, 101, 102, 160]);
+
+        let mut pids = app_pids(100, &table);
+        pids.sort();
+        assert_eq!(pids, vec![100, 101, 102, 150, 160]);
+
+        // Only a group leader pulls in the rest of its group
+        assert_eq!(app_pids(102, &table), vec![102]);
+        assert_eq!(app_pids(999, &table), vec![999]);
+    }
+
+    #[test]
+    fn parses_rss_and_threads_from_status() {
+        let status = "Name:\tnode\nVmPeak:\t 9000 kB\nVmRSS:\t  2048 kB\nThreads:\t12\n";
+        assert_eq!(parse_status(status), (2048 * 1024, 12));
+        // Kernel threads have no VmRSS line
+        assert_eq!(parse_status("Name:\tkthreadd\nThreads:\t1\n"), (0, 1));
+        assert_eq!(parse_status(""), (0, 0));
+    }
+
+    #[test]
+    fn only_waits_for_a_cpu_baseline_when_asked() {
+        let app: TauriApp = serde_json::from_value(serde_json::json!({
+            "id": "self",
+            "name": "Test process",
+            "description": "",
+            "path": "/",
+            "executable": "cargo",
+            "status": "Running",
+            "pid": std::process::id(),
+        }))
+        .unwrap();
+        let registry = MetricsRegistry::default();
+
+        let started = Instant::now();
+        let quick = sample_apps(&registry, [&app], false).unwrap();
+        assert!(started.elapsed() < FIRST_SAMPLE_GAP);
+        assert_eq!(quick["self"].cpu_percent, 0.0);
+        assert!(quick["self"].rss_bytes > 0);
+
+        // That sample left a baseline behind, so this doesn't wait either
+        let started = Instant::now();
+        sample_apps(&registry, [&app], true).unwrap();
+        assert!(started.elapsed() < FIRST_SAMPLE_GAP);
+
+        let started = Instant::now();
+        sample_apps(&MetricsRegistry::default(), [&app], true).unwrap();
+        assert!(started.elapsed() >= FIRST_SAMPLE_GAP);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::HubError;
+
+// How often we re-check the process group while waiting for it to exit
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// How long we keep waiting after SIGKILL before giving up
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct ManagedProcess {
+    // None for processes re-adopted from a previous hub session: they are not
+    // our children, so we can only watch them through /proc
+    pub child: Option<Child>,
+    pub pid: u32,
+    pub pgid: i32,
+    // Start time from /proc/<pid>/stat, used to tell the process apart from a
+    // later one that happens to reuse its pid
+    pub start_time: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ProcessExit {
+    Status(ExitStatus),
+    // Adopted processes are reaped by someone else, so we never see a status
+    Unknown,
+}
+
+impl ManagedProcess {
+    pub fn adopt(pid: u32, pgid: i32, start_time: Option<u64>) -> Self {
+        ManagedProcess {
+            child: None,
+            pid,
+            pgid,
+            start_time,
+        }
+    }
+
+    // Ok(None) while the process is still running
+    pub fn try_wait(&mut self) -> io::Result<Option<ProcessExit>> {
+        match self.child.as_mut() {
+            Some(child) => Ok(child.try_wait()?.map(ProcessExit::Status)),
+            None => {
+                if is_same_process(self.pid, self.start_time) {
+                    Ok(None)
+                } else {
+                    Ok(Some(ProcessExit::Unknown))
+                }
+            }
+        }
+    }
+}
+
+pub type ProcessRegistry = Mutex<HashMap<String, ManagedProcess>>;
+
+// Spawn the command as the leader of a new process group so that everything it
+// starts (vite, cargo, the app binary itself) can be signalled together
+pub fn spawn_in_new_group(cmd: &mut Command) -> io::Result<ManagedProcess> {
+    let child = cmd.process_group(0).spawn()?;
+    let pid = child.id();
+
+    Ok(ManagedProcess {
+        child: Some(child),
+        pid,
+        pgid: pid as i32,
+        start_time: read_proc_stat(pid).map(|stat| stat.start_time),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcStat {
+    pub ppid: u32,
+    pub pgid: i32,
+    // utime + stime, in clock ticks
+    pub cpu_ticks: u64,
+    pub start_time: u64,
+}
+
+// Parse the fields we need out of /proc/<pid>/stat. The command name in field 2
+// may itself contain spaces or parentheses, so split after the last ')'.
+fn parse_proc_stat(stat: &str) -> Option<ProcStat> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+
+    // fields[0] is field 3 (state) in proc(5) numbering
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(ProcStat {
+        ppid: fields.get(1)?.parse().ok()?,
+        pgid: fields.get(2)?.parse().ok()?,
+        cpu_ticks: utime + stime,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+pub fn read_proc_stat(pid: u32) -> Option<ProcStat> {
+    parse_proc_stat(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+// True if `pid` is alive and still has the start time we recorded. Without a
+// recorded start time a reused pid can't be ruled out, so it doesn't count.
+pub fn is_same_process(pid: u32, start_time: Option<u64>) -> bool {
+    match (read_proc_stat(pid), start_time) {
+        (Some(stat), Some(expected)) => stat.start_time == expected,
+        _ => false,
+    }
+}
+
+fn program_stem(arg: &str) -> Option<String> {
+    Path::new(arg)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+}
+
+// Look for a process group leader running `executable` from `workdir`. Script
+// launchers like pnpm show up as `node .../pnpm.cjs`, so the first two argv
+// entries are compared by file stem.
+pub fn find_group_leader(executable: &str, workdir: &Path) -> Option<(u32, ProcStat)> {
+    let wanted = program_stem(executable)?;
+    let workdir = fs::canonicalize(workdir).unwrap_or_else(|_| workdir.to_path_buf());
+
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let pid: u32 = match entry.file_name().to_string_lossy().parse() {
+            Ok(pid) => pid,
+            Err(_) => continue,
+        };
+
+        let stat = match read_proc_stat(pid) {
+            Some(stat) if stat.pgid == pid as i32 => stat,
+            _ => continue,
+        };
+
+        let cwd: PathBuf = match fs::read_link(entry.path().join("cwd")) {
+            Ok(cwd) => cwd,
+            Err(_) => continue,
+        };
+        if cwd != workdir {
+            continue;
+        }
+
+        let cmdline = fs::read(entry.path().join("cmdline")).unwrap_or_default();
+        let matches = cmdline
+            .split(|b| *b == 0)
+            .take(2)
+            .filter_map(|arg| program_stem(&String::from_utf8_lossy(arg)))
+            .any(|stem| stem == wanted);
+        if matches {
+            return Some((pid, stat));
+        }
+    }
+
+    None
+}
+
+fn signal_group(pgid: i32, signal: libc::c_int) -> io::Result<()> {
+    // A negative pid addresses the whole process group
+    let result = unsafe { libc::kill(-pgid, signal) };
+    if result == 0 {
+        return Ok(());
+    }
+
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::ESRCH) {
+        // Nothing left in the group, which is what we wanted anyway
+        Ok(())
+    } else {
+        Err(err)
+    }
+}
+
+fn group_alive(pgid: i32) -> bool {
+    let result = unsafe { libc::kill(-pgid, 0) };
+    // EPERM still means a process with that group id exists
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// Reap our direct child if it has exited and report whether anything in the
+// group is still around. The child has to be reaped first, otherwise its
+// zombie keeps the group alive.
+fn group_exited(process: &mut ManagedProcess) -> bool {
+    let _ = process.try_wait();
+    !group_alive(process.pgid)
+}
+
+fn wait_for_group(process: &mut ManagedProcess, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if group_exited(process) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// How the group leader exited, once terminate has reaped it
+fn reaped(process: &mut ManagedProcess) -> ProcessExit {
+    process.try_wait().ok().flatten().unwrap_or(ProcessExit::Unknown)
+}
+
+// Terminate the whole process tree: SIGTERM the group, give it `grace` to shut
+// down cleanly, then SIGKILL whatever is left. Only returns Ok once nothing in
+// the group is running anymore, with the leader's exit status if we saw it.
+pub fn terminate(mut process: ManagedProcess, grace: Duration) -> Result<ProcessExit, HubError> {
+    if group_exited(&mut process) {
+        return Ok(reaped(&mut process));
+    }
+
+    signal_group(process.pgid, libc::SIGTERM).map_err(|e| {
+        HubError::io(&format!("Failed to send SIGTERM to process group {}", process.pgid), e)
+    })?;
+
+    if wait_for_group(&mut process, grace) {
+        return Ok(reaped(&mut process));
+    }
+
+    eprintln!(
+        "Process group {} still running after {:?}, sending SIGKILL",
+        process.pgid, grace
+    );
+    signal_group(process.pgid, libc::SIGKILL).map_err(|e| {
+        HubError::io(&format!("Failed to send SIGKILL to process group {}", process.pgid), e)
+    })?;
+
+    if wait_for_group(&mut process, KILL_TIMEOUT) {
+        Ok(reaped(&mut process))
+    } else {
+        Err(HubError::from(format!(
+            "Process group {} did not exit after SIGKILL",
+            process.pgid
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stat_with_awkward_command_names() {
+        let stat = "4242 (tauri (dev) ) app) S 4200 4242 4242 0 -1 4194560 1200 0 0 0 150 25 0 0 20 0 7 0 987654 1000000 2500 18446744073709551615\n";
+        let parsed = parse_proc_stat(stat).unwrap();
+        assert_eq!(parsed.ppid, 4200);
+        assert_eq!(parsed.pgid, 4242);
+        assert_eq!(parsed.cpu_ticks, 175);
+        assert_eq!(parsed.start_time, 987654);
+
+        assert!(parse_proc_stat("4242 (cut off) S 1 2").is_none());
+        assert!(parse_proc_stat("no parenthesis at all").is_none());
+    }
+
+    #[test]
+    fn only_a_matching_start_time_is_the_same_process() {
+        let pid = std::process::id();
+        let start_time = read_proc_stat(pid).unwrap().start_time;
+        assert!(is_same_process(pid, Some(start_time)));
+        assert!(!is_same_process(pid, Some(start_time + 1)));
+        assert!(!is_same_process(pid, None));
+        assert!(!is_same_process(u32::MAX, Some(start_time)));
+    }
+
+    #[test]
+    fn terminate_reports_how_the_leader_exited() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let process = spawn_in_new_group(Command::new("sleep").arg("30")).unwrap();
+        match terminate(process, Duration::from_secs(5)).unwrap() {
+            ProcessExit::Status(status) => assert_eq!(status.signal(), Some(libc::SIGTERM)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{expand_home, TauriApp};
+
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+
+// One way of starting an app, e.g. "dev" (pnpm run tauri:dev), "release"
+// (the built binary), "appimage" or a custom script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaunchProfile {
+    pub name: String,
+    pub executable: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    // Relative paths are resolved against the app's path; None means the app's path
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+}
+
+// The profile matching what launch_app used to hard-code before profiles existed
+pub fn legacy_profile(executable: &str) -> LaunchProfile {
+    let args: &[&str] = match executable {
+        // Support running package.json scripts like: pnpm run tauri:dev
+        "pnpm" => &["run", "tauri:dev"],
+        // Support cargo tauri dev
+        "cargo" => &["tauri", "dev"],
+        _ => &[],
+    };
+
+    LaunchProfile {
+        name: DEFAULT_PROFILE_NAME.to_string(),
+        executable: executable.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        env: HashMap::new(),
+        working_dir: None,
+    }
+}
+
+// Give apps saved before launch profiles existed (or registered without any) a
+// default profile that behaves exactly like the old launcher. Returns true if
+// the app was changed.
+pub fn ensure_profiles(app: &mut TauriApp) -> bool {
+    if !app.profiles.is_empty() {
+        return false;
+    }
+
+    app.profiles.push(legacy_profile(&app.executable));
+    app.default_profile = Some(DEFAULT_PROFILE_NAME.to_string());
+    true
+}
+
+// Pick the requested profile, falling back to the app's default and then to
+// the first profile it has
+pub fn select_profile<'a>(
+    app: &'a TauriApp,
+    requested: Option<&str>,
+) -> Result<&'a LaunchProfile, String> {
+    if let Some(name) = requested {
+        return app
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("App '{}' has no launch profile named '{}'", app.name, name));
+    }
+
+    app.default_profile
+        .as_deref()
+        .and_then(|name| app.profiles.iter().find(|p| p.name == name))
+        .or_else(|| app.profiles.first())
+        .ok_or_else(|| format!("App '{}' has no launch profiles", app.name))
+}
+
+pub fn resolve_working_dir(app_path: &Path, profile: &LaunchProfile) -> PathBuf {
+    let app_dir = expand_home(app_path);
+    match &profile.working_dir {
+        Some(dir) => app_dir.join(expand_home(dir)),
+        None => app_dir,
+    }
+}
+
+// Relative executables with a path component (./script.sh, target/release/app)
+// are resolved against the working directory, bare names are looked up on PATH
+pub fn resolve_executable(workdir: &Path, executable: &str) -> PathBuf {
+    let expanded = expand_home(Path::new(executable));
+    if expanded.is_relative() && expanded.components().count() > 1 {
+        workdir.join(expanded)
+    } else {
+        expanded
+    }
+}
+
+pub fn build_command(app: &TauriApp, profile: &LaunchProfile) -> Command {
+    let workdir = resolve_working_dir(&app.path, profile);
+    let mut cmd = Command::new(resolve_executable(&workdir, &profile.executable));
+    cmd.args(&profile.args)
+        .envs(&profile.env)
+        .current_dir(workdir);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn app() -> TauriApp {
+        serde_json::from_value(json!({
+            "id": "app-1",
+            "name": "My App",
+            "description": "",
+            "path": "/nonexistent/my-app",
+            "executable": "pnpm",
+            "status": "Stopped",
+            "profiles": [
+                { "name": "dev", "executable": "pnpm", "args": ["run", "tauri:dev"] },
+                {
+                    "name": "release",
+                    "executable": "target/release/my-app",
+                    "args": ["--verbose"],
+                    "env": { "RUST_LOG": "debug" },
+                    "working_dir": "src-tauri",
+                },
+            ],
+            "default_profile": "release",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn selects_the_requested_or_default_profile() {
+        let mut app = app();
+        assert_eq!(select_profile(&app, None).unwrap().name, "release");
+        assert_eq!(select_profile(&app, Some("dev")).unwrap().name, "dev");
+        assert!(select_profile(&app, Some("appimage")).is_err());
+
+        app.default_profile = None;
+        assert_eq!(select_profile(&app, None).unwrap().name, "dev");
+        app.profiles.clear();
+        assert!(select_profile(&app, None).is_err());
+    }
+
+    #[test]
+    fn builds_commands_from_profiles() {
+        let app = app();
+        let release = select_profile(&app, Some("release")).unwrap();
+        let cmd = build_command(&app, release);
+        assert_eq!(cmd.get_program(), "/nonexistent/my-app/src-tauri/target/release/my-app");
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["--verbose"]);
+        assert_eq!(cmd.get_envs().collect::<Vec<_>>(), [("RUST_LOG".as_ref(), Some("debug".as_ref()))]);
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/nonexistent/my-app/src-tauri")));
+
+        let dev = select_profile(&app, Some("dev")).unwrap();
+        let cmd = build_command(&app, dev);
+        assert_eq!(cmd.get_program(), "pnpm");
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/nonexistent/my-app")));
+    }
+}
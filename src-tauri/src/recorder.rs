@@ -0,0 +1,603 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use hound::{WavReader, WavSpec, WavWriter};
+use serde::Serialize;
+
+use crate::error::HubError;
+
+pub const RECORDING_LEVEL_EVENT: &str = "recording-level";
+
+// input_device values starting with this read a WAV file instead of a microphone
+pub const FAKE_INPUT_PREFIX: &str = "file:";
+
+const LEVELS_PER_SECOND: u32 = 20;
+// Frames the fake input delivers at a time, about what a device period holds
+const FILE_CHUNK_FRAMES: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioDevice {
+    pub name: String,
+    pub is_default: bool,
+}
+
+// Input level over the last 1/LEVELS_PER_SECOND of a second, 0.0 to 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct InputLevel {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputSource {
+    // An input device by name, or the default one
+    Device(Option<String>),
+    // Samples from a WAV file, for running without a microphone. `realtime`
+    // paces them like a live device; otherwise the whole file is delivered
+    // at once.
+    File { path: PathBuf, realtime: bool },
+}
+
+impl InputSource {
+    pub fn from_setting(input_device: Option<&str>) -> Self {
+        match input_device {
+            Some(device) => match device.strip_prefix(FAKE_INPUT_PREFIX) {
+                Some(path) => InputSource::File { path: PathBuf::from(path), realtime: true },
+                None => InputSource::Device(Some(device.to_string())),
+            },
+            None => InputSource::Device(None),
+        }
+    }
+}
+
+fn audio_error(what: &str, e: impl std::fmt::Display) -> HubError {
+    HubError::Io { message: format!("Failed to {}: {}", what, e) }
+}
+
+pub fn list_devices() -> Result<Vec<AudioDevice>, HubError> {
+    let host = cpal::default_host();
+    let default = host.default_input_device().and_then(|d| d.name().ok());
+    let devices = host
+        .input_devices()
+        .map_err(|e| audio_error("list audio devices", e))?;
+    Ok(devices
+        .filter_map(|d| d.name().ok())
+        .map(|name| AudioDevice { is_default: Some(&name) == default.as_ref(), name })
+        .collect())
+}
+
+// Average interleaved frames down to one channel
+fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+// Linear interpolation between sample rates, carried across chunks
+struct Resampler {
+    // Input samples per output sample
+    step: f64,
+    // Position of the next output sample; 0.0 is `last`, the final sample of
+    // the previous chunk
+    pos: f64,
+    last: Option<f32>,
+}
+
+impl Resampler {
+    fn new(from: u32, to: u32) -> Self {
+        Resampler { step: from as f64 / to as f64, pos: 0.0, last: None }
+    }
+
+    fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        if self.step == 1.0 {
+            out.extend_from_slice(input);
+            return;
+        }
+        // The chunk with the previous chunk's last sample in front of it
+        let offset = usize::from(self.last.is_some());
+        let get = |i: usize| if i < offset { self.last.unwrap_or_default() } else { input[i - offset] };
+        let len = input.len() + offset;
+        if len == 0 {
+            return;
+        }
+
+        while (self.pos as usize) + 1 < len {
+            let i = self.pos as usize;
+            let frac = (self.pos - i as f64) as f32;
+            out.push(get(i) + (get(i + 1) - get(i)) * frac);
+            self.pos += self.step;
+        }
+        self.pos -= (len - 1) as f64;
+        self.last = Some(get(len - 1));
+    }
+}
+
+// Interleaved input samples as delivered by the device, converted to f32
+type Chunk = Vec<f32>;
+
+// How many chunks can wait for the writer thread; a device period is
+// typically 5-20 ms, so this is a few seconds of slack
+const QUEUE_CHUNKS: usize = 256;
+// Capacity of the buffers handed to the audio callback up front: a device
+// period of stereo input, so the callback doesn't allocate in the common case
+const SPARE_CHUNK_SAMPLES: usize = FILE_CHUNK_FRAMES * 2;
+
+#[derive(Debug, Clone, Copy)]
+struct InputFormat {
+    channels: usize,
+    rate: u32,
+}
+
+// The real-time side of a recording. It runs in the audio callback, so it
+// never locks or blocks: chunks go to the writer thread over a bounded
+// channel and emptied buffers come back for reuse.
+struct Capture {
+    paused: Arc<AtomicBool>,
+    chunks: SyncSender<Chunk>,
+    spare: Receiver<Chunk>,
+    // Samples lost because the writer thread fell behind
+    dropped: Arc<AtomicU64>,
+}
+
+impl Capture {
+    // Paused samples are dropped, so the recording has no gap or click
+    fn push<T: Copy>(&self, data: &[T], convert: impl Fn(T) -> f32) {
+        if self.paused.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut chunk = self.spare.try_recv().unwrap_or_default();
+        chunk.clear();
+        chunk.extend(data.iter().map(|&s| convert(s)));
+        if self.chunks.try_send(chunk).is_err() {
+            self.dropped.fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+// The writer thread's end: mono samples at the recording's rate go into the
+// WAV file and the level meter
+struct Sink {
+    writer: WavWriter<BufWriter<File>>,
+    error: Option<String>,
+    // Samples per level event
+    level_window: usize,
+    level_samples: usize,
+    peak: f32,
+    sum_squares: f64,
+    on_level: Box<dyn Fn(InputLevel) + Send>,
+}
+
+impl Sink {
+    fn new(
+        writer: WavWriter<BufWriter<File>>,
+        level_window: usize,
+        on_level: Box<dyn Fn(InputLevel) + Send>,
+    ) -> Self {
+        Sink {
+            writer,
+            error: None,
+            level_window: level_window.max(1),
+            level_samples: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+            on_level,
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            let sample = sample.clamp(-1.0, 1.0);
+            if let Err(e) = self.writer.write_sample((sample * i16::MAX as f32) as i16) {
+                self.error.get_or_insert_with(|| e.to_string());
+            }
+
+            self.peak = self.peak.max(sample.abs());
+            self.sum_squares += (sample * sample) as f64;
+            self.level_samples += 1;
+            if self.level_samples == self.level_window {
+                (self.on_level)(InputLevel {
+                    peak: self.peak,
+                    rms: (self.sum_squares / self.level_window as f64).sqrt() as f32,
+                });
+                self.level_samples = 0;
+                self.peak = 0.0;
+                self.sum_squares = 0.0;
+            }
+        }
+    }
+
+    // Write the WAV header sizes and close the file
+    fn finish(self) -> Result<(), HubError> {
+        self.writer.finalize().map_err(|e| audio_error("finish the recording", e))?;
+        match self.error {
+            Some(e) => Err(audio_error("write the recording", e)),
+            None => Ok(()),
+        }
+    }
+}
+
+// Downmix and resample chunks until the capture side hangs up, then finish
+// the file
+fn run_writer(
+    mut sink: Sink,
+    format: InputFormat,
+    rate: u32,
+    chunks: Receiver<Chunk>,
+    spare: SyncSender<Chunk>,
+    dropped: Arc<AtomicU64>,
+) -> Result<(), HubError> {
+    let mut resampler = Resampler::new(format.rate, rate);
+    let mut out = Vec::new();
+    for chunk in chunks {
+        out.clear();
+        resampler.process(&downmix(&chunk, format.channels), &mut out);
+        sink.push(&out);
+        let _ = spare.try_send(chunk);
+    }
+
+    let dropped = dropped.load(Ordering::Relaxed);
+    if dropped > 0 {
+        eprintln!("Recording fell behind, dropped {} input samples", dropped);
+    }
+    sink.finish()
+}
+
+fn open_device(name: Option<&str>) -> Result<cpal::Device, HubError> {
+    let host = cpal::default_host();
+    match name {
+        None => host
+            .default_input_device()
+            .ok_or_else(|| HubError::not_found("No audio input device found")),
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| audio_error("list audio devices", e))?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| HubError::not_found(format!("Audio input device '{}' not found", name))),
+    }
+}
+
+// A configuration recording at `rate` directly if the device offers one,
+// otherwise its default (and the samples get resampled)
+fn pick_config(device: &cpal::Device, rate: u32) -> Result<cpal::SupportedStreamConfig, HubError> {
+    let rate = cpal::SampleRate(rate);
+    let exact = device
+        .supported_input_configs()
+        .map_err(|e| audio_error("read the audio device configuration", e))?
+        .filter(|range| range.min_sample_rate() <= rate && rate <= range.max_sample_rate())
+        .min_by_key(|range| range.channels())
+        .map(|range| range.with_sample_rate(rate));
+    match exact {
+        Some(config) => Ok(config),
+        None => device
+            .default_input_config()
+            .map_err(|e| audio_error("read the audio device configuration", e)),
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    capture: Capture,
+) -> Result<cpal::Stream, HubError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| capture.push(data, |s| s.to_sample::<f32>()),
+            |e| eprintln!("Audio input error: {}", e),
+            None,
+        )
+        .map_err(|e| audio_error("open the audio input", e))
+}
+
+fn start_device(name: Option<&str>, capture: Capture, rate: u32) -> Result<(cpal::Stream, InputFormat), HubError> {
+    let device = open_device(name)?;
+    let supported = pick_config(&device, rate)?;
+    let config = supported.config();
+    let format = InputFormat { channels: config.channels as usize, rate: config.sample_rate.0 };
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, capture),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, capture),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, capture),
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, capture),
+        other => Err(audio_error("open the audio input", format!("unsupported sample format {}", other))),
+    }?;
+    stream.play().map_err(|e| audio_error("start the audio input", e))?;
+    Ok((stream, format))
+}
+
+// cpal streams can't leave the thread that built them, so each device
+// recording gets its own thread that holds the stream until told to stop
+fn run_device(
+    name: Option<String>,
+    capture: Capture,
+    rate: u32,
+    ready: Sender<Result<InputFormat, HubError>>,
+    stop: Receiver<()>,
+) {
+    match start_device(name.as_deref(), capture, rate) {
+        Ok((stream, format)) => {
+            let _ = ready.send(Ok(format));
+            let _ = stop.recv();
+            drop(stream);
+        }
+        Err(e) => {
+            let _ = ready.send(Err(e));
+        }
+    }
+}
+
+fn read_wav(path: &Path) -> Result<(WavSpec, Vec<f32>), hound::Error> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    Ok((spec, samples))
+}
+
+fn run_file(
+    path: PathBuf,
+    realtime: bool,
+    capture: Capture,
+    ready: Sender<Result<InputFormat, HubError>>,
+    stop: Receiver<()>,
+) {
+    let (spec, samples) = match read_wav(&path) {
+        Ok(wav) => wav,
+        Err(e) => {
+            let _ = ready.send(Err(audio_error(&format!("read fake input {:?}", path), e)));
+            return;
+        }
+    };
+    let channels = spec.channels as usize;
+    let _ = ready.send(Ok(InputFormat { channels, rate: spec.sample_rate }));
+
+    let chunk_time = Duration::from_secs_f64(FILE_CHUNK_FRAMES as f64 / spec.sample_rate as f64);
+    for chunk in samples.chunks(FILE_CHUNK_FRAMES * channels) {
+        if realtime {
+            capture.push(chunk, |s| s);
+            if stop.recv_timeout(chunk_time).is_ok() {
+                return;
+            }
+        } else {
+            // Nothing paces the writer here, so wait for room instead of
+            // dropping chunks like a live device would
+            if !capture.paused.load(Ordering::SeqCst) && capture.chunks.send(chunk.to_vec()).is_err() {
+                return;
+            }
+        }
+    }
+    // Like a microphone gone quiet: nothing more until stopped
+    let _ = stop.recv();
+}
+
+// An in-progress recording: mono 16-bit WAV at the requested rate
+pub struct Recorder {
+    paused: Arc<AtomicBool>,
+    stop: Option<Sender<()>>,
+    capture_thread: Option<JoinHandle<()>>,
+    writer_thread: Option<JoinHandle<Result<(), HubError>>>,
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder")
+            .field("paused", &self.paused.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+impl Recorder {
+    // `on_level` is called from the writer thread, never the audio callback
+    pub fn start(
+        source: InputSource,
+        path: &Path,
+        rate: u32,
+        on_level: impl Fn(InputLevel) + Send + 'static,
+    ) -> Result<Recorder, HubError> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = WavWriter::create(path, spec).map_err(|e| audio_error(&format!("create {:?}", path), e))?;
+
+        let paused = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (chunks_tx, chunks_rx) = mpsc::sync_channel(QUEUE_CHUNKS);
+        let (spare_tx, spare_rx) = mpsc::sync_channel(QUEUE_CHUNKS);
+        for _ in 0..QUEUE_CHUNKS {
+            let _ = spare_tx.try_send(Vec::with_capacity(SPARE_CHUNK_SAMPLES));
+        }
+        let capture = Capture { paused: paused.clone(), chunks: chunks_tx, spare: spare_rx, dropped: dropped.clone() };
+
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let capture_thread = thread::spawn(move || match source {
+            InputSource::Device(name) => run_device(name, capture, rate, ready_tx, stop_rx),
+            InputSource::File { path, realtime } => run_file(path, realtime, capture, ready_tx, stop_rx),
+        });
+
+        let format = match ready_rx.recv().unwrap_or_else(|_| Err(HubError::from("Recording thread exited"))) {
+            Ok(format) => format,
+            Err(e) => {
+                let _ = capture_thread.join();
+                drop(writer);
+                // Don't leave the empty WAV behind
+                let _ = std::fs::remove_file(path);
+                return Err(e);
+            }
+        };
+
+        let sink = Sink::new(writer, (rate / LEVELS_PER_SECOND) as usize, Box::new(on_level));
+        let writer_thread = thread::spawn(move || run_writer(sink, format, rate, chunks_rx, spare_tx, dropped));
+        Ok(Recorder {
+            paused,
+            stop: Some(stop_tx),
+            capture_thread: Some(capture_thread),
+            writer_thread: Some(writer_thread),
+        })
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    // Stop the input and finish the WAV file
+    pub fn stop(mut self) -> Result<(), HubError> {
+        self.shutdown()
+    }
+
+    // Stopping the capture thread drops its end of the queue, which lets the
+    // writer thread drain it and finish
+    fn shutdown(&mut self) -> Result<(), HubError> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.capture_thread.take() {
+            let _ = thread.join();
+        }
+        match self.writer_thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(HubError::from("Recording writer thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            eprintln!("{}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+    use std::sync::Mutex;
+
+    fn write_wav(path: &Path, spec: WavSpec, samples: &[i16]) {
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        for &s in samples {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn resamples_across_chunks() {
+        let input: Vec<f32> = (0..12).map(|i| i as f32).collect();
+        let mut resampler = Resampler::new(48_000, 16_000);
+        let mut out = Vec::new();
+        for chunk in input.chunks(5) {
+            resampler.process(chunk, &mut out);
+        }
+        assert_eq!(out, vec![0.0, 3.0, 6.0, 9.0]);
+
+        let mut up = Resampler::new(8_000, 16_000);
+        let mut out = Vec::new();
+        up.process(&[0.0, 1.0], &mut out);
+        up.process(&[2.0], &mut out);
+        assert_eq!(out, vec![0.0, 0.5, 1.0, 1.5]);
+
+        assert_eq!(downmix(&[0.5, -0.5, 1.0, 0.0], 2), vec![0.0, 0.5]);
+    }
+
+    #[test]
+    fn drops_samples_while_paused_and_reports_levels() {
+        let fx = Fixture::new("recorder-sink");
+        let path = fx.dir.join("sink.wav");
+        let spec = WavSpec { channels: 1, sample_rate: 16_000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let seen = levels.clone();
+        let sink = Sink::new(
+            WavWriter::create(&path, spec).unwrap(),
+            4,
+            Box::new(move |level| seen.lock().unwrap().push(level)),
+        );
+
+        let (chunks_tx, chunks_rx) = mpsc::sync_channel(QUEUE_CHUNKS);
+        let (spare_tx, spare_rx) = mpsc::sync_channel(QUEUE_CHUNKS);
+        let capture = Capture {
+            paused: Arc::new(AtomicBool::new(false)),
+            chunks: chunks_tx,
+            spare: spare_rx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        let dropped = capture.dropped.clone();
+
+        // Stereo at twice the recording's rate, so every 4 values are 1 sample
+        capture.push(&[0.5f32; 8], |s| s);
+        capture.push(&[-0.5f32; 8], |s| s);
+        capture.paused.store(true, Ordering::SeqCst);
+        capture.push(&[1.0f32; 100], |s| s);
+        capture.paused.store(false, Ordering::SeqCst);
+        capture.push(&[0i16; 8], |s| s as f32);
+        drop(capture);
+
+        let format = InputFormat { channels: 2, rate: 32_000 };
+        run_writer(sink, format, 16_000, chunks_rx, spare_tx, dropped).unwrap();
+
+        let recorded = WavReader::open(&path).unwrap().samples::<i16>().count();
+        assert_eq!(recorded, 6);
+        assert_eq!(levels.lock().unwrap()[0], InputLevel { peak: 0.5, rms: 0.5 });
+    }
+
+    #[test]
+    fn records_from_a_fake_input_file() {
+        let fx = Fixture::new("recorder-file");
+        let input = fx.dir.join("input.wav");
+        let output = fx.dir.join("output.wav");
+        let spec = WavSpec { channels: 2, sample_rate: 48_000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        write_wav(&input, spec, &vec![8_192; 4_800 * 2]);
+
+        let source = InputSource::from_setting(Some(&format!("file:{}", input.display())));
+        assert_eq!(source, InputSource::File { path: input.clone(), realtime: true });
+
+        let source = InputSource::File { path: input.clone(), realtime: false };
+        let recorder = Recorder::start(source, &output, 16_000, |_| {}).unwrap();
+        recorder.stop().unwrap();
+
+        let mut reader = WavReader::open(&output).unwrap();
+        assert_eq!(reader.spec().sample_rate, 16_000);
+        assert_eq!(reader.spec().channels, 1);
+        let samples: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), 1_600);
+        assert!(samples.iter().all(|&s| (8_190..=8_192).contains(&s)));
+
+        let missing = InputSource::File { path: fx.dir.join("missing.wav"), realtime: false };
+        assert!(Recorder::start(missing, &output, 16_000, |_| {}).is_err());
+    }
+}
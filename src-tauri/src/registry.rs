@@ -0,0 +1,559 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::HubError;
+use crate::profiles;
+use crate::{get_app_data_dir, TauriApp};
+
+// Version 0 is the bare `{ "<id>": TauriApp }` map written before the envelope
+// existed. Bump this and add a step to MIGRATIONS whenever the on-disk format
+// changes in a way serde defaults can't absorb.
+pub const CURRENT_VERSION: u32 = 1;
+
+// Previous versions of registry.json kept as registry.json.bak.1 (newest) .. .bak.N
+const BACKUP_GENERATIONS: usize = 5;
+
+type Migration = fn(Value) -> Result<Value, String>;
+
+// MIGRATIONS[n] upgrades a version n document to version n + 1
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [migrate_v0_to_v1];
+
+#[derive(Deserialize)]
+struct RegistryFile {
+    version: u32,
+    apps: HashMap<String, TauriApp>,
+}
+
+#[derive(Serialize)]
+struct RegistryFileRef<'a> {
+    version: u32,
+    apps: &'a HashMap<String, TauriApp>,
+}
+
+pub struct LoadedRegistry {
+    pub apps: HashMap<String, TauriApp>,
+    // Version the file was in before migrating, None if it was already current
+    pub migrated_from: Option<u32>,
+}
+
+// v0 -> v1: wrap the bare map in the versioned envelope and give every app the
+// launch profile matching the old hard-coded pnpm/cargo arguments
+fn migrate_v0_to_v1(value: Value) -> Result<Value, String> {
+    let mut apps = match value {
+        Value::Object(apps) => apps,
+        _ => return Err("Expected a map of apps in version 0 registry".to_string()),
+    };
+
+    for app in apps.values_mut() {
+        let app = app
+            .as_object_mut()
+            .ok_or("Expected an app object in version 0 registry")?;
+
+        let has_profiles = app
+            .get("profiles")
+            .and_then(Value::as_array)
+            .is_some_and(|p| !p.is_empty());
+        if has_profiles {
+            continue;
+        }
+
+        let executable = app
+            .get("executable")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let profile = serde_json::to_value(profiles::legacy_profile(&executable))
+            .map_err(|e| format!("Failed to build default profile: {}", e))?;
+        app.insert("profiles".to_string(), Value::Array(vec![profile]));
+        app.insert(
+            "default_profile".to_string(),
+            Value::String(profiles::DEFAULT_PROFILE_NAME.to_string()),
+        );
+    }
+
+    Ok(json!({ "version": 1, "apps": apps }))
+}
+
+fn detect_version(value: &Value) -> Result<u32, String> {
+    let version = value
+        .as_object()
+        .filter(|obj| obj.get("apps").is_some_and(Value::is_object))
+        .and_then(|obj| obj.get("version"))
+        .and_then(Value::as_u64);
+
+    match version {
+        Some(v) => u32::try_from(v).map_err(|_| format!("Invalid registry version {}", v)),
+        None if value.is_object() => Ok(0),
+        None => Err("Registry is not a JSON object".to_string()),
+    }
+}
+
+pub fn load_from_str(content: &str) -> Result<LoadedRegistry, String> {
+    let mut value: Value = serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse registry: {}", e))?;
+
+    let from_version = detect_version(&value)?;
+    if from_version > CURRENT_VERSION {
+        return Err(format!(
+            "Registry version {} is newer than this hub supports ({})",
+            from_version, CURRENT_VERSION
+        ));
+    }
+
+    for migration in &MIGRATIONS[from_version as usize..] {
+        value = migration(value)?;
+    }
+
+    let file: RegistryFile = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse registry: {}", e))?;
+    debug_assert_eq!(file.version, CURRENT_VERSION);
+
+    let mut apps = file.apps;
+    // Apps registered without profiles still get the default one
+    for app in apps.values_mut() {
+        profiles::ensure_profiles(app);
+    }
+
+    Ok(LoadedRegistry {
+        apps,
+        migrated_from: (from_version < CURRENT_VERSION).then_some(from_version),
+    })
+}
+
+pub fn to_string(apps: &HashMap<String, TauriApp>) -> Result<String, String> {
+    let file = RegistryFileRef {
+        version: CURRENT_VERSION,
+        apps,
+    };
+    serde_json::to_string_pretty(&file)
+        .map_err(|e| format!("Failed to serialize registry: {}", e))
+}
+
+pub fn get_registry_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, HubError> {
+    Ok(get_app_data_dir(app_handle)?.join("registry.json"))
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    sibling_path(path, &format!(".bak.{}", generation))
+}
+
+pub struct RegistryLoad {
+    pub apps: HashMap<String, TauriApp>,
+    // Set when registry.json was unusable and a backup generation was loaded
+    pub recovered_from: Option<PathBuf>,
+    // Version the loaded file was migrated from, if any
+    pub migrated_from: Option<u32>,
+}
+
+fn load_file(path: &Path) -> Result<LoadedRegistry, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    load_from_str(&content).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+// Load registry.json, falling back to the newest backup generation that still
+// parses. Only fails if neither the registry nor any backup is usable.
+pub fn load_from_path(path: &Path) -> Result<RegistryLoad, String> {
+    let backups: Vec<PathBuf> = (1..=BACKUP_GENERATIONS)
+        .map(|n| backup_path(path, n))
+        .filter(|p| p.exists())
+        .collect();
+
+    if !path.exists() && backups.is_empty() {
+        return Ok(RegistryLoad {
+            apps: HashMap::new(),
+            recovered_from: None,
+            migrated_from: None,
+        });
+    }
+
+    let mut errors = Vec::new();
+    let primary = if path.exists() {
+        load_file(path)
+    } else {
+        Err(format!("{:?} is missing", path))
+    };
+    match primary {
+        Ok(loaded) => {
+            return Ok(RegistryLoad {
+                apps: loaded.apps,
+                recovered_from: None,
+                migrated_from: loaded.migrated_from,
+            })
+        }
+        Err(e) => errors.push(e),
+    }
+
+    for backup in backups {
+        match load_file(&backup) {
+            Ok(loaded) => {
+                return Ok(RegistryLoad {
+                    apps: loaded.apps,
+                    recovered_from: Some(backup),
+                    migrated_from: loaded.migrated_from,
+                })
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    Err(format!("No usable registry found: {}", errors.join("; ")))
+}
+
+pub fn load_registry(app_handle: &tauri::AppHandle) -> Result<RegistryLoad, HubError> {
+    let registry_path = get_registry_path(app_handle)?;
+
+    let loaded = load_from_path(&registry_path).map_err(HubError::parse)?;
+
+    // The registry is readable at this point, so failing to write it back only
+    // gets logged; the next save tries again
+    if let Some(backup) = &loaded.recovered_from {
+        // Keep the broken file for inspection and write the recovered data back
+        match set_aside_unreadable(&registry_path) {
+            Ok(aside) => {
+                if let Some(aside) = aside {
+                    eprintln!("Moved unreadable registry to {:?}", aside);
+                }
+                if let Err(e) = write_to_path(&registry_path, &loaded.apps, false) {
+                    eprintln!("{}", e);
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+        eprintln!("Recovered registry from backup {:?}", backup);
+    } else if let Some(from_version) = loaded.migrated_from {
+        // Keep the original around in case the migration got something wrong
+        let backup_path = sibling_path(&registry_path, &format!(".v{}.bak", from_version));
+        match fs::copy(&registry_path, &backup_path) {
+            Ok(_) => match write_to_path(&registry_path, &loaded.apps, false) {
+                Ok(()) => eprintln!(
+                    "Migrated registry from version {} to {} (backup at {:?})",
+                    from_version, CURRENT_VERSION, backup_path
+                ),
+                Err(e) => eprintln!("{}", e),
+            },
+            // Leave the old file alone; it is migrated again on the next start
+            Err(e) => eprintln!("Failed to back up registry before migration: {}", e),
+        }
+    }
+
+    Ok(loaded)
+}
+
+// Move an unreadable registry out of the way so the next save doesn't destroy it
+pub fn set_aside_unreadable(registry_path: &Path) -> Result<Option<PathBuf>, HubError> {
+    if !registry_path.exists() {
+        return Ok(None);
+    }
+
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let aside_path = sibling_path(registry_path, &format!(".unreadable-{}", timestamp));
+    fs::rename(registry_path, &aside_path)
+        .map_err(|e| HubError::io("Failed to move unreadable registry aside", e))?;
+    Ok(Some(aside_path))
+}
+
+// Shift registry.json.bak.1..N-1 up by one and make the current registry the
+// new .bak.1. A hard link keeps registry.json in place until the rename below
+// replaces it.
+fn rotate_backups(path: &Path) -> io::Result<()> {
+    for n in (1..BACKUP_GENERATIONS).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+
+    if path.exists() {
+        let newest = backup_path(path, 1);
+        if fs::hard_link(path, &newest).is_err() {
+            fs::copy(path, &newest)?;
+        }
+    }
+    Ok(())
+}
+
+// Write to a temp file in the same directory, fsync it and atomically rename
+// it over registry.json, so a crash leaves either the old or the new file.
+// Backups are only rotated when asked to, so frequent status writes don't
+// push every older generation out.
+fn write_to_path(path: &Path, apps: &HashMap<String, TauriApp>, rotate: bool) -> Result<(), HubError> {
+    let content = to_string(apps)?;
+    let dir = path.parent().ok_or("Registry path has no parent directory")?;
+    let tmp_path = sibling_path(path, &format!(".tmp-{}", std::process::id()));
+
+    let write_tmp = || -> io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    };
+    if let Err(e) = write_tmp() {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(HubError::io("Failed to write registry", e));
+    }
+
+    if rotate {
+        if let Err(e) = rotate_backups(path) {
+            // Losing a backup generation is not worth failing the save for
+            eprintln!("Failed to rotate registry backups: {}", e);
+        }
+    }
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(HubError::io("Failed to replace registry", e));
+    }
+
+    // Persist the rename itself
+    File::open(dir)
+        .and_then(|d| d.sync_all())
+        .map_err(|e| HubError::io("Failed to sync registry directory", e))
+}
+
+// Save after the set of apps or their configuration changed, keeping the
+// previous registry as a backup
+pub fn save_to_path(path: &Path, apps: &HashMap<String, TauriApp>) -> Result<(), HubError> {
+    write_to_path(path, apps, true)
+}
+
+pub fn save_registry(app_handle: &tauri::AppHandle, apps: &HashMap<String, TauriApp>) -> Result<(), HubError> {
+    save_to_path(&get_registry_path(app_handle)?, apps)
+}
+
+// Save runtime state (status, pid) only; the backups are left as they are
+pub fn save_registry_status(app_handle: &tauri::AppHandle, apps: &HashMap<String, TauriApp>) -> Result<(), HubError> {
+    write_to_path(&get_registry_path(app_handle)?, apps, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+    use crate::AppStatus;
+
+    // registry.json as written by the original hub: a bare map, no profiles
+    const V0_ORIGINAL: &str = r#"{
+        "pdfsearch": {
+            "id": "pdfsearch",
+            "name": "PDF Search",
+            "description": "Search PDFs",
+            "path": "~/apps/pdfsearch-tauri",
+            "executable": "pnpm",
+            "icon": "📚",
+            "status": "Running"
+        },
+        "notes": {
+            "id": "notes",
+            "name": "Notes",
+            "description": "",
+            "path": "/opt/notes",
+            "executable": "cargo",
+            "icon": null,
+            "status": "Stopped"
+        }
+    }"#;
+
+    // Still a bare map, but with the fields added for process tracking,
+    // launch profiles and restart policies
+    const V0_WITH_PROFILES: &str = r#"{
+        "helper": {
+            "id": "helper",
+            "name": "Helper",
+            "description": "",
+            "path": "/opt/helper",
+            "executable": "./helper",
+            "icon": null,
+            "status": "Error",
+            "stop_grace_period_secs": 10,
+            "last_exit": { "code": 1, "signal": null, "exited_at": "2025-01-01T00:00:00+00:00" },
+            "pid": null,
+            "start_time": null,
+            "profiles": [
+                { "name": "release", "executable": "./helper", "args": ["--quiet"], "env": {}, "working_dir": null }
+            ],
+            "default_profile": "release",
+            "active_profile": "release",
+            "restart_policy": { "mode": "Always", "max_retries": 3 },
+            "status_reason": "Exited with code 1"
+        }
+    }"#;
+
+    const V1: &str = r#"{
+        "version": 1,
+        "apps": {
+            "helper": {
+                "id": "helper",
+                "name": "Helper",
+                "description": "",
+                "path": "/opt/helper",
+                "executable": "./helper",
+                "icon": null,
+                "status": "Stopped",
+                "profiles": [
+                    { "name": "dev", "executable": "pnpm", "args": ["run", "dev"] }
+                ],
+                "default_profile": "dev"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn loads_original_v0_registry() {
+        let loaded = load_from_str(V0_ORIGINAL).unwrap();
+        assert_eq!(loaded.migrated_from, Some(0));
+        assert_eq!(loaded.apps.len(), 2);
+
+        let pdfsearch = &loaded.apps["pdfsearch"];
+        assert!(matches!(pdfsearch.status, AppStatus::Running));
+        assert_eq!(pdfsearch.default_profile.as_deref(), Some("default"));
+        assert_eq!(pdfsearch.profiles.len(), 1);
+        assert_eq!(pdfsearch.profiles[0].executable, "pnpm");
+        assert_eq!(pdfsearch.profiles[0].args, vec!["run", "tauri:dev"]);
+
+        let notes = &loaded.apps["notes"];
+        assert_eq!(notes.profiles[0].args, vec!["tauri", "dev"]);
+        assert_eq!(notes.stop_grace_period_secs, 5);
+    }
+
+    #[test]
+    fn loads_v0_registry_with_profiles_without_replacing_them() {
+        let loaded = load_from_str(V0_WITH_PROFILES).unwrap();
+        assert_eq!(loaded.migrated_from, Some(0));
+
+        let helper = &loaded.apps["helper"];
+        assert_eq!(helper.profiles.len(), 1);
+        assert_eq!(helper.profiles[0].name, "release");
+        assert_eq!(helper.default_profile.as_deref(), Some("release"));
+        assert_eq!(helper.stop_grace_period_secs, 10);
+        assert_eq!(helper.restart_policy.max_retries, 3);
+        assert_eq!(helper.last_exit.as_ref().and_then(|e| e.code), Some(1));
+    }
+
+    #[test]
+    fn loads_current_version_without_migrating() {
+        let loaded = load_from_str(V1).unwrap();
+        assert_eq!(loaded.migrated_from, None);
+        assert_eq!(loaded.apps["helper"].profiles[0].args, vec!["run", "dev"]);
+    }
+
+    #[test]
+    fn empty_v0_registry_migrates() {
+        let loaded = load_from_str("{}").unwrap();
+        assert_eq!(loaded.migrated_from, Some(0));
+        assert!(loaded.apps.is_empty());
+    }
+
+    #[test]
+    fn round_trips_through_current_format() {
+        let apps = load_from_str(V0_ORIGINAL).unwrap().apps;
+        let saved = to_string(&apps).unwrap();
+
+        let reloaded = load_from_str(&saved).unwrap();
+        assert_eq!(reloaded.migrated_from, None);
+        assert_eq!(reloaded.apps.len(), apps.len());
+        assert_eq!(
+            reloaded.apps["pdfsearch"].profiles[0].args,
+            apps["pdfsearch"].profiles[0].args
+        );
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let err = load_from_str(r#"{ "version": 99, "apps": {} }"#)
+            .err()
+            .unwrap();
+        assert!(err.contains("newer"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(load_from_str("[]").is_err());
+        assert!(load_from_str(r#"{ "broken": "#).is_err());
+    }
+
+    #[test]
+    fn saves_keep_backup_generations() {
+        let fx = Fixture::new("registry-backups");
+        let dir = &fx.dir;
+        let path = dir.join("registry.json");
+        let mut apps = load_from_str(V1).unwrap().apps;
+
+        for n in 0..(BACKUP_GENERATIONS + 2) {
+            apps.get_mut("helper").unwrap().description = format!("save {}", n);
+            save_to_path(&path, &apps).unwrap();
+        }
+
+        let newest = load_file(&path).unwrap().apps;
+        assert_eq!(newest["helper"].description, format!("save {}", BACKUP_GENERATIONS + 1));
+        let previous = load_file(&backup_path(&path, 1)).unwrap().apps;
+        assert_eq!(previous["helper"].description, format!("save {}", BACKUP_GENERATIONS));
+        assert!(backup_path(&path, BACKUP_GENERATIONS).exists());
+        assert!(!backup_path(&path, BACKUP_GENERATIONS + 1).exists());
+    }
+
+    #[test]
+    fn status_saves_leave_backups_alone() {
+        let fx = Fixture::new("registry-status-saves");
+        let dir = &fx.dir;
+        let path = dir.join("registry.json");
+        let mut apps = load_from_str(V1).unwrap().apps;
+
+        save_to_path(&path, &apps).unwrap();
+        apps.get_mut("helper").unwrap().description = "configured".to_string();
+        save_to_path(&path, &apps).unwrap();
+        for status in [AppStatus::Running, AppStatus::Stopped, AppStatus::Running] {
+            apps.get_mut("helper").unwrap().status = status;
+            write_to_path(&path, &apps, false).unwrap();
+        }
+
+        assert!(matches!(load_file(&path).unwrap().apps["helper"].status, AppStatus::Running));
+        let previous = load_file(&backup_path(&path, 1)).unwrap().apps;
+        assert_ne!(previous["helper"].description, "configured");
+        assert!(!backup_path(&path, 2).exists());
+    }
+
+    #[test]
+    fn write_failures_are_io_errors() {
+        let fx = Fixture::new("registry-io");
+        let apps = load_from_str(V1).unwrap().apps;
+        let err = save_to_path(&fx.dir.join("missing/registry.json"), &apps).unwrap_err();
+        assert_eq!(serde_json::to_value(&err).unwrap()["code"], "io");
+    }
+
+    #[test]
+    fn falls_back_to_newest_valid_backup() {
+        let fx = Fixture::new("registry-fallback");
+        let dir = &fx.dir;
+        let path = dir.join("registry.json");
+        let apps = load_from_str(V1).unwrap().apps;
+
+        save_to_path(&path, &apps).unwrap();
+        save_to_path(&path, &apps).unwrap();
+        fs::write(backup_path(&path, 2), "garbage").unwrap();
+        // Simulate a write that was cut off half way
+        fs::write(&path, r#"{ "version": 1, "apps": { "hel"#).unwrap();
+
+        let loaded = load_from_path(&path).unwrap();
+        assert_eq!(loaded.recovered_from, Some(backup_path(&path, 1)));
+        assert!(loaded.apps.contains_key("helper"));
+    }
+
+    #[test]
+    fn missing_registry_without_backups_is_empty() {
+        let fx = Fixture::new("registry-missing");
+        let dir = &fx.dir;
+        let loaded = load_from_path(&dir.join("registry.json")).unwrap();
+        assert!(loaded.apps.is_empty());
+        assert!(loaded.recovered_from.is_none());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum RestartMode {
+    #[default]
+    Never,
+    OnFailure,
+    Always,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    // Restarts attempted in a row before giving up, 0 means no limit
+    pub max_retries: u32,
+    // Delay before the first restart, doubled on every further attempt
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    // Failures within the window that count as a crash loop
+    pub crash_loop_threshold: u32,
+    pub crash_loop_window_secs: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            mode: RestartMode::Never,
+            max_retries: 5,
+            initial_backoff_secs: 1,
+            max_backoff_secs: 60,
+            crash_loop_threshold: 5,
+            crash_loop_window_secs: 300,
+        }
+    }
+}
+
+// Per-app bookkeeping for the current run of restarts, not persisted
+#[derive(Debug, Default)]
+pub struct RestartState {
+    attempts: u32,
+    failures: VecDeque<Instant>,
+    started_at: Option<Instant>,
+    // Bumped whenever the user starts or stops the app by hand, so restarts
+    // scheduled before that are dropped
+    generation: u64,
+}
+
+pub type RestartRegistry = Mutex<HashMap<String, RestartState>>;
+
+#[derive(Debug)]
+pub enum RestartDecision {
+    None,
+    Restart { delay: Duration, attempt: u32 },
+    // Stop trying and leave the app in Error with this reason
+    GiveUp(String),
+}
+
+fn backoff(policy: &RestartPolicy, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    let secs = policy
+        .initial_backoff_secs
+        .saturating_mul(factor)
+        .min(policy.max_backoff_secs);
+    Duration::from_secs(secs)
+}
+
+impl RestartState {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn record_start(&mut self) {
+        self.started_at = Some(Instant::now());
+    }
+
+    // Called for launches requested by the user: forget earlier failures and
+    // invalidate any pending restart
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.failures.clear();
+        self.generation += 1;
+    }
+
+    pub fn on_exit(&mut self, policy: &RestartPolicy, failed: bool) -> RestartDecision {
+        let now = Instant::now();
+        let window = Duration::from_secs(policy.crash_loop_window_secs);
+
+        // A run that outlived the crash-loop window counts as healthy
+        if let Some(started_at) = self.started_at.take() {
+            if now.duration_since(started_at) >= window {
+                self.attempts = 0;
+            }
+        }
+
+        if failed {
+            self.failures.push_back(now);
+        }
+        while let Some(first) = self.failures.front() {
+            if now.duration_since(*first) > window {
+                self.failures.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let wants_restart = match policy.mode {
+            RestartMode::Never => false,
+            RestartMode::OnFailure => failed,
+            RestartMode::Always => true,
+        };
+        if !wants_restart {
+            self.attempts = 0;
+            return RestartDecision::None;
+        }
+
+        if policy.crash_loop_threshold > 0
+            && self.failures.len() >= policy.crash_loop_threshold as usize
+        {
+            return RestartDecision::GiveUp(format!(
+                "Crash loop detected: {} failures within {}s",
+                self.failures.len(),
+                policy.crash_loop_window_secs
+            ));
+        }
+
+        if policy.max_retries > 0 && self.attempts >= policy.max_retries {
+            return RestartDecision::GiveUp(format!(
+                "Gave up after {} restart attempts",
+                self.attempts
+            ));
+        }
+
+        self.attempts += 1;
+        RestartDecision::Restart {
+            delay: backoff(policy, self.attempts),
+            attempt: self.attempts,
+        }
+    }
+}
+
+// Relaunch the app after `delay` unless the user started or stopped it in the
+// meantime
+pub fn schedule_restart(
+    app_handle: AppHandle,
+    app_id: String,
+    profile: Option<String>,
+    delay: Duration,
+    generation: u64,
+) {
+    thread::spawn(move || {
+        thread::sleep(delay);
+
+        let still_wanted = {
+            let restarts = app_handle.state::<RestartRegistry>();
+            let restarts = match restarts.lock() {
+                Ok(r) => r,
+                Err(_) => return,
+            };
+            restarts
+                .get(&app_id)
+                .map(|state| state.generation == generation)
+                .unwrap_or(false)
+        };
+        if !still_wanted {
+            return;
+        }
+
+        if let Err(e) = crate::start_app(&app_handle, &app_id, profile.as_deref()) {
+            eprintln!("Failed to restart app '{}': {}", app_id, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(mode: RestartMode) -> RestartPolicy {
+        RestartPolicy { mode, ..RestartPolicy::default() }
+    }
+
+    fn delay(decision: RestartDecision) -> u64 {
+        match decision {
+            RestartDecision::Restart { delay, .. } => delay.as_secs(),
+            other => panic!("expected a restart, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let policy = RestartPolicy { initial_backoff_secs: 2, max_backoff_secs: 30, ..RestartPolicy::default() };
+        let delays: Vec<u64> = (1..=6).map(|attempt| backoff(&policy, attempt).as_secs()).collect();
+        assert_eq!(delays, vec![2, 4, 8, 16, 30, 30]);
+        // Huge attempt counts saturate instead of overflowing
+        assert_eq!(backoff(&policy, u32::MAX).as_secs(), 30);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let policy = RestartPolicy { max_retries: 3, crash_loop_threshold: 0, ..policy(RestartMode::OnFailure) };
+        let mut state = RestartState::default();
+
+        let delays: Vec<u64> = (0..3).map(|_| delay(state.on_exit(&policy, true))).collect();
+        assert_eq!(delays, vec![1, 2, 4]);
+        assert!(matches!(state.on_exit(&policy, true), RestartDecision::GiveUp(_)));
+
+        // A launch by the user starts counting from scratch
+        state.reset();
+        assert_eq!(delay(state.on_exit(&policy, true)), 1);
+    }
+
+    #[test]
+    fn restarts_only_when_the_mode_asks_for_it() {
+        let mut state = RestartState::default();
+        assert!(matches!(state.on_exit(&policy(RestartMode::Never), true), RestartDecision::None));
+        assert!(matches!(state.on_exit(&policy(RestartMode::OnFailure), false), RestartDecision::None));
+        assert!(matches!(state.on_exit(&policy(RestartMode::Always), false), RestartDecision::Restart { attempt: 1, .. }));
+    }
+
+    #[test]
+    fn detects_crash_loops_within_the_window() {
+        let policy = RestartPolicy {
+            max_retries: 0,
+            crash_loop_threshold: 3,
+            crash_loop_window_secs: 10,
+            ..policy(RestartMode::OnFailure)
+        };
+        let mut state = RestartState::default();
+
+        delay(state.on_exit(&policy, true));
+        delay(state.on_exit(&policy, true));
+        assert!(matches!(state.on_exit(&policy, true), RestartDecision::GiveUp(_)));
+
+        // Failures older than the window no longer count
+        let window = Duration::from_secs(policy.crash_loop_window_secs);
+        let long_ago = Instant::now() - window - Duration::from_secs(1);
+        state.failures.iter_mut().for_each(|at| *at = long_ago);
+        delay(state.on_exit(&policy, true));
+        assert_eq!(state.failures.len(), 1);
+    }
+
+    #[test]
+    fn a_long_healthy_run_resets_the_attempts() {
+        let policy = RestartPolicy { max_retries: 2, crash_loop_window_secs: 10, ..policy(RestartMode::Always) };
+        let mut state = RestartState::default();
+
+        assert_eq!(delay(state.on_exit(&policy, false)), 1);
+        assert_eq!(delay(state.on_exit(&policy, false)), 2);
+
+        let window = Duration::from_secs(policy.crash_loop_window_secs);
+        state.started_at = Some(Instant::now() - window);
+        assert!(matches!(state.on_exit(&policy, false), RestartDecision::Restart { attempt: 1, .. }));
+    }
+}
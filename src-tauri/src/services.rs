@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::error::{self, HubError};
+use crate::systemd::{self, SystemdBus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceScope {
+    // Managed by the system manager; changing it usually needs root
+    System,
+    // Managed by the user's own manager (systemctl --user)
+    User,
+}
+
+// A systemd unit the hub can show and control, declared in settings.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceDefinition {
+    pub id: String,
+    pub display_name: String,
+    // Full unit name, e.g. "ollama.service"
+    pub unit: String,
+    pub scope: ServiceScope,
+    // Run start/stop/enable/disable through pkexec. Ignored for user units.
+    #[serde(default)]
+    pub pkexec: bool,
+}
+
+impl ServiceDefinition {
+    fn new(id: &str, display_name: &str, unit: &str, scope: ServiceScope, pkexec: bool) -> Self {
+        ServiceDefinition {
+            id: id.to_string(),
+            display_name: display_name.to_string(),
+            unit: unit.to_string(),
+            scope,
+            pkexec,
+        }
+    }
+}
+
+// The services the hub has always had buttons for
+pub fn default_services() -> Vec<ServiceDefinition> {
+    vec![
+        ServiceDefinition::new("opensnitch", "OpenSnitch", "opensnitchd.service", ServiceScope::System, true),
+        ServiceDefinition::new("open-webui", "Open WebUI", "open-webui.service", ServiceScope::System, true),
+        ServiceDefinition::new("ollama", "Ollama", "ollama.service", ServiceScope::System, true),
+        ServiceDefinition::new("docker", "Docker", "docker.service", ServiceScope::System, true),
+        ServiceDefinition::new("docker-desktop", "Docker Desktop", "docker-desktop.service", ServiceScope::User, false),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+    Enable,
+    Disable,
+}
+
+impl ServiceAction {
+    pub fn verb(self) -> &'static str {
+        match self {
+            ServiceAction::Start => "start",
+            ServiceAction::Stop => "stop",
+            ServiceAction::Restart => "restart",
+            ServiceAction::Enable => "enable",
+            ServiceAction::Disable => "disable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    #[serde(flatten)]
+    pub service: ServiceDefinition,
+    // "loaded", or "not-found" when the unit isn't installed
+    pub load_state: String,
+    // "active", "inactive", "failed", "activating", ...
+    pub active_state: String,
+    pub sub_state: String,
+    // "enabled", "disabled", "static", ... (empty for transient units)
+    pub unit_file_state: String,
+    pub active: bool,
+    pub enabled: bool,
+    // The rest is only known for .service units
+    pub main_pid: Option<u32>,
+    // Memory and CPU time as counted by the unit's cgroup
+    pub memory_bytes: Option<u64>,
+    pub cpu_usage_nsec: Option<u64>,
+    // Exit status of the main process the last time it exited
+    pub exit_status: Option<i32>,
+    // "success", "exit-code", "signal", "timeout", ...
+    pub result: Option<String>,
+    // When the unit last became active, RFC 3339
+    pub active_since: Option<String>,
+}
+
+pub fn find<'a>(services: &'a [ServiceDefinition], id: &str) -> Result<&'a ServiceDefinition, HubError> {
+    services
+        .iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| HubError::not_found(format!("Service '{}' is not configured", id)))
+}
+
+// Journal commands take the unit name the user sees in the logs
+pub fn find_by_unit<'a>(services: &'a [ServiceDefinition], unit: &str) -> Result<&'a ServiceDefinition, HubError> {
+    services
+        .iter()
+        .find(|s| s.unit == unit)
+        .ok_or_else(|| HubError::not_found(format!("Unit '{}' is not a configured service", unit)))
+}
+
+// systemctl with --user for user units
+fn systemctl(scope: ServiceScope) -> Command {
+    let mut cmd = Command::new("systemctl");
+    if scope == ServiceScope::User {
+        cmd.arg("--user");
+    }
+    cmd
+}
+
+pub fn is_enabled(unit_file_state: &str) -> bool {
+    matches!(unit_file_state, "enabled" | "enabled-runtime" | "alias")
+}
+
+// `systemctl show` prints one Key=Value pair per line
+fn parse_show(output: &str) -> HashMap<&str, &str> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+fn status_from_show(service: &ServiceDefinition, output: &str) -> ServiceStatus {
+    let props = parse_show(output);
+    let prop = |key: &str| props.get(key).copied().unwrap_or_default().to_string();
+    // Unset values show up as "[not set]" or an empty string and fail to parse
+    let number = |key: &str| props.get(key).and_then(|v| v.parse::<u64>().ok());
+
+    let active_state = prop("ActiveState");
+    let unit_file_state = prop("UnitFileState");
+    let exec_main_code = number("ExecMainCode").unwrap_or(0);
+    ServiceStatus {
+        service: service.clone(),
+        load_state: prop("LoadState"),
+        active: active_state == "active",
+        enabled: is_enabled(&unit_file_state),
+        active_state,
+        sub_state: prop("SubState"),
+        unit_file_state,
+        main_pid: number("MainPID").filter(|&pid| pid != 0).map(|pid| pid as u32),
+        memory_bytes: number("MemoryCurrent"),
+        cpu_usage_nsec: number("CPUUsageNSec"),
+        exit_status: props
+            .get("ExecMainStatus")
+            .and_then(|v| v.parse().ok())
+            .filter(|_| exec_main_code != 0),
+        result: props.get("Result").filter(|v| !v.is_empty()).map(|v| v.to_string()),
+        // "@<seconds>" with --timestamp=unix, empty if the unit never started
+        active_since: props
+            .get("ActiveEnterTimestamp")
+            .and_then(|v| v.strip_prefix('@'))
+            .and_then(|v| v.parse::<u64>().ok())
+            .and_then(|secs| systemd::timestamp_from_usec(secs * 1_000_000)),
+    }
+}
+
+// Status over D-Bus when the unit's bus is reachable, otherwise from systemctl
+pub fn status(bus: &SystemdBus, service: &ServiceDefinition) -> Result<ServiceStatus, HubError> {
+    match bus.connection(service.scope) {
+        Some(conn) => systemd::status(&conn, service),
+        None => status_via_systemctl(service),
+    }
+}
+
+fn status_via_systemctl(service: &ServiceDefinition) -> Result<ServiceStatus, HubError> {
+    let mut cmd = systemctl(service.scope);
+    cmd.arg("show")
+        .arg("--timestamp=unix")
+        .arg("--property=LoadState,ActiveState,SubState,UnitFileState,MainPID,MemoryCurrent,CPUUsageNSec,ExecMainCode,ExecMainStatus,Result,ActiveEnterTimestamp")
+        .arg(&service.unit);
+    let output = error::run(&mut cmd, &format!("check {} status", service.display_name))?;
+    Ok(status_from_show(service, &String::from_utf8_lossy(&output.stdout)))
+}
+
+pub fn run_action(service: &ServiceDefinition, action: ServiceAction) -> Result<(), HubError> {
+    let mut cmd = if service.scope == ServiceScope::System && service.pkexec {
+        let mut cmd = Command::new("pkexec");
+        cmd.arg("systemctl");
+        cmd
+    } else {
+        systemctl(service.scope)
+    };
+    cmd.arg(action.verb()).arg(&service.unit);
+
+    error::run(&mut cmd, &format!("{} {}", action.verb(), service.display_name))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_systemctl_show_output() {
+        let docker = &default_services()[3];
+        let status = status_from_show(
+            docker,
+            "LoadState=loaded\nActiveState=failed\nSubState=failed\nUnitFileState=enabled\nMainPID=0\n\
+             MemoryCurrent=[not set]\nCPUUsageNSec=1500000\nExecMainCode=1\nExecMainStatus=2\nResult=exit-code\n\
+             ActiveEnterTimestamp=@1714550400\n",
+        );
+        assert_eq!(status.active_state, "failed");
+        assert!(!status.active);
+        assert!(status.enabled);
+        assert_eq!(status.main_pid, None);
+        assert_eq!(status.memory_bytes, None);
+        assert_eq!(status.cpu_usage_nsec, Some(1_500_000));
+        assert_eq!(status.exit_status, Some(2));
+        assert_eq!(status.result.as_deref(), Some("exit-code"));
+        assert_eq!(status.active_since.as_deref(), Some("2024-05-01T08:00:00+00:00"));
+
+        let missing = status_from_show(docker, "LoadState=not-found\nActiveState=inactive\nSubState=dead\nUnitFileState=\n");
+        assert_eq!(missing.load_state, "not-found");
+        assert!(!missing.enabled);
+        assert_eq!(missing.active_since, None);
+    }
+
+    #[test]
+    fn deserializes_a_minimal_definition() {
+        let service: ServiceDefinition = serde_json::from_str(
+            r#"{"id":"syncthing","display_name":"Syncthing","unit":"syncthing.service","scope":"user"}"#,
+        )
+        .unwrap();
+        assert_eq!(service.scope, ServiceScope::User);
+        assert!(!service.pkexec);
+
+        let services = default_services();
+        assert!(find(&services, "ollama").is_ok());
+        assert!(matches!(find(&services, "nope"), Err(HubError::NotFound { .. })));
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::alerts::{self, AlertRule};
+use crate::error::HubError;
+use crate::get_app_data_dir;
+use crate::services::{self, ServiceDefinition};
+use crate::stt::SttSettings;
+
+// Hub-wide preferences, persisted next to registry.json as settings.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HubSettings {
+    // Directories scanned by discover_apps
+    pub discovery_roots: Vec<PathBuf>,
+    // Resource thresholds checked by the alert watcher
+    pub alert_rules: Vec<AlertRule>,
+    // systemd units shown on the dashboard and controlled by service_action
+    pub services: Vec<ServiceDefinition>,
+    // How recordings are transcribed
+    pub stt: SttSettings,
+}
+
+impl Default for HubSettings {
+    fn default() -> Self {
+        let mut discovery_roots = Vec::new();
+        // During development the hub's own apps/ directory is the obvious place to look
+        if cfg!(debug_assertions) {
+            discovery_roots.push(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../apps"));
+        }
+        HubSettings {
+            discovery_roots,
+            alert_rules: alerts::default_alert_rules(),
+            services: services::default_services(),
+            stt: SttSettings::default(),
+        }
+    }
+}
+
+pub type SettingsState = Mutex<HubSettings>;
+
+fn get_settings_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, HubError> {
+    Ok(get_app_data_dir(app_handle)?.join("settings.json"))
+}
+
+pub fn load_settings(app_handle: &tauri::AppHandle) -> Result<HubSettings, HubError> {
+    let settings_path = get_settings_path(app_handle)?;
+
+    if !settings_path.exists() {
+        return Ok(HubSettings::default());
+    }
+
+    let content = fs::read_to_string(&settings_path)
+        .map_err(|e| HubError::io("Failed to read settings", e))?;
+
+    serde_json::from_str(&content)
+        .map_err(|e| HubError::parse(format!("Failed to parse settings: {}", e)))
+}
+
+pub fn save_settings(app_handle: &tauri::AppHandle, settings: &HubSettings) -> Result<(), HubError> {
+    let settings_path = get_settings_path(app_handle)?;
+
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    // Same fsync + rename dance as the registry so a crash can't truncate it
+    let tmp_path = settings_path.with_extension("json.tmp");
+    let write_tmp = || -> io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &settings_path)
+    };
+    write_tmp().map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        HubError::io("Failed to write settings", e)
+    })
+}
@@ -0,0 +1,352 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::HubError;
+use crate::expand_home;
+use crate::recorder::FAKE_INPUT_PREFIX;
+use crate::transcriber::{self, Cancellation, Segment, Transcriber, Transcript};
+use crate::validation::{self, FieldError, FieldErrorCode};
+
+// Where the openai-whisper CLI was installed before this was configurable
+const LEGACY_WHISPER_BIN: &str = "~/.pyenv/versions/whisper-py312/bin/whisper";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SttEngine {
+    // The Python openai-whisper CLI
+    WhisperCli,
+    // whisper.cpp's whisper-cli (or main) binary
+    WhisperCpp,
+    // An OpenAI-compatible /v1/audio/transcriptions endpoint
+    Http,
+}
+
+impl SttEngine {
+    // Sample rate recordings are made at. whisper.cpp only reads 16 kHz WAV;
+    // the others resample on their own.
+    pub fn sample_rate(self) -> u32 {
+        match self {
+            SttEngine::WhisperCpp => 16_000,
+            SttEngine::WhisperCli | SttEngine::Http => 48_000,
+        }
+    }
+
+    // Whether a recording made at `rate` can be fed to this engine
+    pub fn accepts_sample_rate(self, rate: u32) -> bool {
+        match self {
+            SttEngine::WhisperCpp => rate == self.sample_rate(),
+            SttEngine::WhisperCli | SttEngine::Http => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SttDevice {
+    // CUDA when an NVIDIA driver is loaded, otherwise the CPU
+    Auto,
+    // CUDA, falling back to the CPU if it isn't available or fails
+    Cuda,
+    Cpu,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SttSettings {
+    pub engine: SttEngine,
+    // Executable of the CLI engines: a bare name looked up on PATH or a path ('~' allowed)
+    pub binary: String,
+    // "tiny", "base", "small", ... for whisper and the HTTP engine; the path
+    // of a ggml model file for whisper.cpp
+    pub model: String,
+    // Spoken language as an ISO 639-1 code; None lets Whisper detect it
+    pub language: Option<String>,
+    pub device: SttDevice,
+    // Directory the CLI engines write their .txt transcripts to
+    pub output_dir: PathBuf,
+    // Base URL of the HTTP engine, e.g. "http://localhost:8000"
+    pub endpoint: String,
+    // Sent as a bearer token by the HTTP engine
+    pub api_key: Option<String>,
+    // Keep the recorded WAV after a successful transcription
+    pub keep_audio: bool,
+    // Name of the input device to record from, as listed by
+    // list_audio_devices; "file:<path>" replays a WAV file instead. None
+    // records from the default device.
+    pub input_device: Option<String>,
+}
+
+impl Default for SttSettings {
+    fn default() -> Self {
+        let binary = if expand_home(Path::new(LEGACY_WHISPER_BIN)).is_file() {
+            LEGACY_WHISPER_BIN
+        } else {
+            "whisper"
+        };
+        SttSettings {
+            engine: SttEngine::WhisperCli,
+            binary: binary.to_string(),
+            model: "small".to_string(),
+            language: None,
+            device: SttDevice::Auto,
+            output_dir: std::env::temp_dir(),
+            endpoint: "http://localhost:8000".to_string(),
+            api_key: None,
+            keep_audio: true,
+            input_device: None,
+        }
+    }
+}
+
+pub fn validate_with_path(settings: &SttSettings, path_var: &OsStr) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    if settings.engine != SttEngine::Http {
+        errors.extend(validation::check_executable("binary", &settings.binary, &home, path_var));
+    }
+
+    if settings.model.trim().is_empty() {
+        errors.push(validation::error("model", FieldErrorCode::Empty, "Model is required"));
+    } else if settings.engine == SttEngine::WhisperCpp && !expand_home(Path::new(&settings.model)).is_file() {
+        errors.push(validation::error(
+            "model",
+            FieldErrorCode::PathNotFound,
+            format!("whisper.cpp model {:?} does not exist", settings.model),
+        ));
+    }
+
+    if settings.engine == SttEngine::Http
+        && !(settings.endpoint.starts_with("http://") || settings.endpoint.starts_with("https://"))
+    {
+        errors.push(validation::error(
+            "endpoint",
+            FieldErrorCode::InvalidValue,
+            format!("Endpoint '{}' is not an http:// or https:// URL", settings.endpoint),
+        ));
+    }
+
+    if let Some(language) = &settings.language {
+        let is_code = (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_lowercase());
+        if !is_code {
+            errors.push(validation::error(
+                "language",
+                FieldErrorCode::InvalidValue,
+                format!("Language '{}' is not an ISO 639-1 code such as 'en'", language),
+            ));
+        }
+    }
+
+    let output_dir = expand_home(&settings.output_dir);
+    if settings.engine == SttEngine::Http {
+        // Nothing is written to disk
+    } else if settings.output_dir.as_os_str().is_empty() {
+        errors.push(validation::error("output_dir", FieldErrorCode::Empty, "Output directory is required"));
+    } else if output_dir.exists() && !output_dir.is_dir() {
+        errors.push(validation::error(
+            "output_dir",
+            FieldErrorCode::NotADirectory,
+            format!("Output directory {:?} is not a directory", output_dir),
+        ));
+    }
+    if let Some(path) = settings.input_device.as_deref().and_then(|d| d.strip_prefix(FAKE_INPUT_PREFIX)) {
+        if !Path::new(path).is_file() {
+            errors.push(validation::error(
+                "input_device",
+                FieldErrorCode::PathNotFound,
+                format!("Fake input file {:?} does not exist", path),
+            ));
+        }
+    }
+    errors
+}
+
+// An archived recording may have been made for another engine: whisper.cpp
+// can't take the 48 kHz audio recorded for the others
+pub fn check_recording(engine: SttEngine, audio: &Path) -> Result<Option<FieldError>, HubError> {
+    let rate = hound::WavReader::open(audio)
+        .map_err(|e| HubError::parse(format!("Failed to read recording {:?}: {}", audio, e)))?
+        .spec()
+        .sample_rate;
+    if engine.accepts_sample_rate(rate) {
+        return Ok(None);
+    }
+    Ok(Some(validation::error(
+        "engine",
+        FieldErrorCode::InvalidValue,
+        format!(
+            "whisper.cpp only reads {} Hz audio, but the recording is {} Hz",
+            engine.sample_rate(),
+            rate
+        ),
+    )))
+}
+
+// Everything that would make a transcription fail, checked before recording
+pub fn validate(settings: &SttSettings) -> Vec<FieldError> {
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    validate_with_path(settings, &path_var)
+}
+
+// Transcribe `audio` with the configured engine and return its raw text
+pub fn transcribe(
+    settings: &SttSettings,
+    audio: &Path,
+    cancel: &Cancellation,
+    on_segment: &mut dyn FnMut(&Segment),
+) -> Result<Transcript, HubError> {
+    transcribe_with(transcriber::from_settings(settings).as_ref(), settings, audio, cancel, on_segment)
+}
+
+fn transcribe_with(
+    engine: &dyn Transcriber,
+    settings: &SttSettings,
+    audio: &Path,
+    cancel: &Cancellation,
+    on_segment: &mut dyn FnMut(&Segment),
+) -> Result<Transcript, HubError> {
+    let mut transcript = engine.transcribe(audio, cancel, on_segment)?;
+    transcript.text = clean_text(&transcript.text);
+    if !settings.keep_audio {
+        if let Err(e) = fs::remove_file(audio) {
+            eprintln!("Failed to delete {:?}: {}", audio, e);
+        }
+    }
+    Ok(transcript)
+}
+
+// Strip the timestamps and dates Whisper sometimes hallucinates into the
+// text, and collapse whitespace so it pastes as one paragraph
+pub fn clean_text(text: &str) -> String {
+    let patterns = [
+        r"\[\d{2}:\d{2}\.\d{3} --> \d{2}:\d{2}\.\d{3}\]",
+        r"\d{1,2}/\d{1,2}/\d{2,4}",  // MM/DD/YYYY or DD/MM/YYYY
+        r"\d{4}-\d{2}-\d{2}",         // YYYY-MM-DD
+        r"\d{1,2}-\d{1,2}-\d{2,4}",  // MM-DD-YYYY or DD-MM-YYYY
+        r"(?i)(january|february|march|april|may|june|july|august|september|october|november|december)\s+\d{1,2},?\s+\d{4}",
+    ];
+    let mut text = text.to_string();
+    for pattern in patterns {
+        text = Regex::new(pattern).unwrap().replace_all(&text, "").to_string();
+    }
+    Regex::new(r"\s+").unwrap().replace_all(text.trim(), " ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+    use crate::transcriber::MockTranscriber;
+
+    #[test]
+    fn reports_each_missing_piece() {
+        let fx = Fixture::new("stt-validate");
+        let dir = &fx.dir;
+        let file = fx.write("not-a-dir", "");
+
+        let settings = SttSettings {
+            binary: "whisper".to_string(),
+            model: " ".to_string(),
+            language: Some("English".to_string()),
+            device: SttDevice::Cuda,
+            output_dir: file,
+            ..SttSettings::default()
+        };
+        let errors = validate_with_path(&settings, dir.as_os_str());
+        let fields: Vec<_> = errors.iter().map(|e| (e.field.as_str(), e.code)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("binary", FieldErrorCode::ExecutableNotFound),
+                ("model", FieldErrorCode::Empty),
+                ("language", FieldErrorCode::InvalidValue),
+                ("output_dir", FieldErrorCode::NotADirectory),
+            ]
+        );
+
+        fx.write_with_mode("whisper", "#!/bin/sh\n", 0o755);
+        let settings = SttSettings {
+            language: Some("en".to_string()),
+            output_dir: dir.join("new"),
+            ..SttSettings::default()
+        };
+        assert!(validate_with_path(&SttSettings { binary: "whisper".to_string(), ..settings.clone() }, dir.as_os_str()).is_empty());
+
+        // Each engine only needs its own pieces
+        let cpp = SttSettings {
+            engine: SttEngine::WhisperCpp,
+            binary: "whisper".to_string(),
+            model: "~/no-such-model.bin".to_string(),
+            ..settings.clone()
+        };
+        let errors = validate_with_path(&cpp, dir.as_os_str());
+        assert_eq!(errors.iter().map(|e| e.code).collect::<Vec<_>>(), vec![FieldErrorCode::PathNotFound]);
+
+        let http = SttSettings {
+            engine: SttEngine::Http,
+            binary: String::new(),
+            endpoint: "localhost:8000".to_string(),
+            ..settings
+        };
+        let errors = validate_with_path(&http, dir.as_os_str());
+        assert_eq!(errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(), vec!["endpoint"]);
+    }
+
+    #[test]
+    fn deletes_the_recording_unless_kept() {
+        let fx = Fixture::new("stt-transcribe");
+        let audio = fx.write("recording_1.wav", "RIFF");
+        let engine = MockTranscriber { text: " hello\n  there ".to_string() };
+        let cancel = Cancellation::default();
+
+        let settings = SttSettings::default();
+        let transcript = transcribe_with(&engine, &settings, &audio, &cancel, &mut |_| {}).unwrap();
+        assert_eq!(transcript.text, "hello there");
+        assert!(audio.exists());
+
+        let settings = SttSettings { keep_audio: false, ..settings };
+        transcribe_with(&engine, &settings, &audio, &cancel, &mut |_| {}).unwrap();
+        assert!(!audio.exists());
+
+        cancel.cancel();
+        let result = transcribe_with(&engine, &settings, &audio, &cancel, &mut |_| {});
+        assert!(matches!(result, Err(HubError::Cancelled { .. })));
+    }
+
+    #[test]
+    fn rejects_recordings_whisper_cpp_cannot_read() {
+        let fx = Fixture::new("stt-rate");
+        let wav = |name: &str, rate: u32| {
+            let path = fx.dir.join(name);
+            let spec = hound::WavSpec {
+                channels: 1,
+                sample_rate: rate,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            hound::WavWriter::create(&path, spec).unwrap().finalize().unwrap();
+            path
+        };
+        let (hifi, lofi) = (wav("48k.wav", 48_000), wav("16k.wav", 16_000));
+
+        let err = check_recording(SttEngine::WhisperCpp, &hifi).unwrap().unwrap();
+        assert_eq!((err.field.as_str(), err.code), ("engine", FieldErrorCode::InvalidValue));
+        assert_eq!(check_recording(SttEngine::WhisperCpp, &lofi).unwrap(), None);
+        assert_eq!(check_recording(SttEngine::WhisperCli, &lofi).unwrap(), None);
+        assert_eq!(check_recording(SttEngine::Http, &hifi).unwrap(), None);
+
+        let garbage = fx.write("garbage.wav", "not a wav");
+        assert!(matches!(check_recording(SttEngine::Http, &garbage), Err(HubError::Parse { .. })));
+    }
+
+    #[test]
+    fn strips_timestamps_and_dates() {
+        assert_eq!(
+            clean_text("[00:00.000 --> 00:02.000]  Call me\n on 2024-05-01 or May 3, 2024  please. "),
+            "Call me on or please."
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::os::unix::process::ExitStatusExt;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::process::{self, ManagedProcess, ProcessExit, ProcessRegistry};
+use crate::profiles;
+use crate::registry::save_registry_status;
+use crate::restart::{self, RestartDecision, RestartRegistry, RestartState};
+use crate::{AppExit, AppRegistry, AppStatus, TauriApp};
+
+const SUPERVISOR_INTERVAL: Duration = Duration::from_millis(500);
+
+pub const APP_STATUS_EVENT: &str = "app-status-changed";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStatusEvent {
+    pub app_id: String,
+    pub status: AppStatus,
+    pub status_reason: Option<String>,
+    pub last_exit: Option<AppExit>,
+}
+
+pub fn emit_app_status(app_handle: &AppHandle, app: &TauriApp) {
+    let event = AppStatusEvent {
+        app_id: app.id.clone(),
+        status: app.status.clone(),
+        status_reason: app.status_reason.clone(),
+        last_exit: app.last_exit.clone(),
+    };
+    if let Err(e) = app_handle.emit(APP_STATUS_EVENT, event) {
+        eprintln!("Failed to emit {} event: {}", APP_STATUS_EVENT, e);
+    }
+}
+
+fn exit_from_process(exit: &ProcessExit) -> AppExit {
+    let (code, signal) = match exit {
+        ProcessExit::Status(status) => (status.code(), status.signal()),
+        ProcessExit::Unknown => (None, None),
+    };
+    AppExit {
+        code,
+        signal,
+        exited_at: chrono::Local::now().to_rfc3339(),
+    }
+}
+
+// The exit stop_app records for a process it terminated. Adopted processes
+// leave no status, so whatever an earlier run left behind is cleared instead.
+pub fn stopped_exit(exit: &ProcessExit) -> Option<AppExit> {
+    match exit {
+        ProcessExit::Status(_) => Some(exit_from_process(exit)),
+        ProcessExit::Unknown => None,
+    }
+}
+
+fn describe_exit(exit: &AppExit) -> String {
+    match (exit.code, exit.signal) {
+        (Some(code), _) => format!("Exited with code {}", code),
+        (None, Some(signal)) => format!("Killed by signal {}", signal),
+        (None, None) => "Exited".to_string(),
+    }
+}
+
+// Only a clean exit of one of our own children counts as Stopped; for adopted
+// processes we can't see the status, so we give them the benefit of the doubt
+fn status_after_exit(exit: &ProcessExit) -> AppStatus {
+    match exit {
+        ProcessExit::Status(status) if !status.success() => AppStatus::Error,
+        _ => AppStatus::Stopped,
+    }
+}
+
+// Update `app` after its process exited and return the delay of the restart
+// its policy asks for, if any
+fn apply_exit(
+    app: &mut TauriApp,
+    state: &mut RestartState,
+    exit: &ProcessExit,
+) -> Option<Duration> {
+    let last_exit = exit_from_process(exit);
+    app.status = status_after_exit(exit);
+    app.status_reason = match app.status {
+        AppStatus::Error => Some(describe_exit(&last_exit)),
+        _ => None,
+    };
+    app.last_exit = Some(last_exit);
+    app.pid = None;
+    app.start_time = None;
+
+    let failed = matches!(app.status, AppStatus::Error);
+    match state.on_exit(&app.restart_policy, failed) {
+        RestartDecision::None => None,
+        RestartDecision::Restart { delay, attempt } => {
+            app.status_reason = Some(format!(
+                "Restarting in {}s (attempt {})",
+                delay.as_secs(),
+                attempt
+            ));
+            Some(delay)
+        }
+        RestartDecision::GiveUp(reason) => {
+            eprintln!("Not restarting app '{}': {}", app.name, reason);
+            app.status = AppStatus::Error;
+            app.status_reason = Some(reason);
+            None
+        }
+    }
+}
+
+// Reap every launched child that has exited since the last pass and return
+// the exit information per app id
+fn collect_exited(app_handle: &AppHandle) -> Vec<(String, ProcessExit)> {
+    let processes = app_handle.state::<ProcessRegistry>();
+    let mut processes = match processes.lock() {
+        Ok(p) => p,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut exited = Vec::new();
+    for (app_id, process) in processes.iter_mut() {
+        match process.try_wait() {
+            Ok(Some(exit)) => exited.push((app_id.clone(), exit)),
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to poll process for app '{}': {}", app_id, e),
+        }
+    }
+
+    for (app_id, _) in &exited {
+        processes.remove(app_id);
+    }
+    exited
+}
+
+fn record_exits(app_handle: &AppHandle, exited: Vec<(String, ProcessExit)>) {
+    let registry = app_handle.state::<AppRegistry>();
+    let restarts = app_handle.state::<RestartRegistry>();
+    let mut apps = match registry.lock() {
+        Ok(a) => a,
+        Err(_) => return,
+    };
+    let mut restarts = match restarts.lock() {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+
+    let mut changed = Vec::new();
+    let mut pending_restarts = Vec::new();
+    for (app_id, exit) in exited {
+        if let Some(app) = apps.get_mut(&app_id) {
+            let state = restarts.entry(app_id.clone()).or_default();
+            if let Some(delay) = apply_exit(app, state, &exit) {
+                pending_restarts.push((
+                    app_id.clone(),
+                    app.active_profile.clone(),
+                    delay,
+                    state.generation(),
+                ));
+            }
+            changed.push(app.clone());
+        }
+    }
+    drop(restarts);
+
+    if changed.is_empty() {
+        return;
+    }
+
+    if let Err(e) = save_registry_status(app_handle, &apps) {
+        eprintln!("Failed to save registry after app exit: {}", e);
+    }
+    drop(apps);
+
+    for app in &changed {
+        emit_app_status(app_handle, app);
+    }
+
+    for (app_id, profile, delay, generation) in pending_restarts {
+        restart::schedule_restart(app_handle.clone(), app_id, profile, delay, generation);
+    }
+}
+
+// Check every app persisted as Running against the live process table. Apps
+// whose process survived are re-adopted and returned so the supervisor keeps
+// watching them; the rest are reset to Stopped.
+pub fn reconcile_running_apps(
+    apps: &mut HashMap<String, TauriApp>,
+) -> HashMap<String, ManagedProcess> {
+    let mut adopted = HashMap::new();
+
+    for app in apps.values_mut() {
+        if !matches!(app.status, AppStatus::Running) {
+            continue;
+        }
+
+        let by_pid = app.pid.and_then(|pid| {
+            if !process::is_same_process(pid, app.start_time) {
+                return None;
+            }
+            process::read_proc_stat(pid).map(|stat| (pid, stat))
+        });
+        let found = by_pid.or_else(|| {
+            let profile = profiles::select_profile(app, app.active_profile.as_deref()).ok()?;
+            let workdir = profiles::resolve_working_dir(&app.path, profile);
+            process::find_group_leader(&profile.executable, &workdir)
+        });
+
+        match found {
+            Some((pid, stat)) => {
+                eprintln!(
+                    "Re-adopted running app '{}' (pid {}, process group {})",
+                    app.name, pid, stat.pgid
+                );
+                app.pid = Some(pid);
+                app.start_time = Some(stat.start_time);
+                adopted.insert(
+                    app.id.clone(),
+                    ManagedProcess::adopt(pid, stat.pgid, Some(stat.start_time)),
+                );
+            }
+            None => {
+                eprintln!(
+                    "App '{}' was persisted as Running but its process is gone, marking it Stopped",
+                    app.name
+                );
+                app.status = AppStatus::Stopped;
+                app.pid = None;
+                app.start_time = None;
+            }
+        }
+    }
+
+    adopted
+}
+
+// Background thread that watches every launched child and keeps AppStatus in
+// sync with what actually happened to the process
+pub fn start_process_supervisor(app_handle: AppHandle) {
+    thread::spawn(move || loop {
+        let exited = collect_exited(&app_handle);
+        if !exited.is_empty() {
+            record_exits(&app_handle, exited);
+        }
+        thread::sleep(SUPERVISOR_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::restart::{RestartMode, RestartPolicy};
+    use std::process::ExitStatus;
+
+    fn app(status: AppStatus) -> TauriApp {
+        let mut app: TauriApp = serde_json::from_value(serde_json::json!({
+            "id": "app-1",
+            "name": "My App",
+            "description": "",
+            "path": "/nonexistent/my-app",
+            "executable": "/nonexistent/my-app/run",
+            "status": "Stopped",
+        }))
+        .unwrap();
+        app.status = status;
+        app
+    }
+
+    // Wait statuses as waitpid(2) reports them
+    fn exited_with(code: i32) -> ProcessExit {
+        ProcessExit::Status(ExitStatus::from_raw(code << 8))
+    }
+
+    fn killed_by(signal: i32) -> ProcessExit {
+        ProcessExit::Status(ExitStatus::from_raw(signal))
+    }
+
+    #[test]
+    fn records_how_the_app_exited() {
+        let mut state = RestartState::default();
+
+        let mut clean = app(AppStatus::Running);
+        clean.pid = Some(42);
+        assert_eq!(apply_exit(&mut clean, &mut state, &exited_with(0)), None);
+        assert!(matches!(clean.status, AppStatus::Stopped));
+        assert_eq!(clean.status_reason, None);
+        assert_eq!(clean.pid, None);
+        assert_eq!(clean.last_exit.as_ref().and_then(|e| e.code), Some(0));
+
+        let mut failed = app(AppStatus::Running);
+        apply_exit(&mut failed, &mut state, &exited_with(3));
+        assert!(matches!(failed.status, AppStatus::Error));
+        assert_eq!(failed.status_reason.as_deref(), Some("Exited with code 3"));
+
+        let mut killed = app(AppStatus::Running);
+        apply_exit(&mut killed, &mut state, &killed_by(9));
+        assert_eq!(killed.status_reason.as_deref(), Some("Killed by signal 9"));
+        assert_eq!(killed.last_exit.as_ref().and_then(|e| e.signal), Some(9));
+
+        // Adopted processes can't tell us how they ended
+        let mut adopted = app(AppStatus::Running);
+        apply_exit(&mut adopted, &mut state, &ProcessExit::Unknown);
+        assert!(matches!(adopted.status, AppStatus::Stopped));
+    }
+
+    #[test]
+    fn schedules_restarts_until_the_policy_gives_up() {
+        let mut state = RestartState::default();
+        let mut app = app(AppStatus::Running);
+        app.restart_policy = RestartPolicy {
+            mode: RestartMode::OnFailure,
+            max_retries: 1,
+            ..RestartPolicy::default()
+        };
+
+        assert_eq!(
+            apply_exit(&mut app, &mut state, &exited_with(1)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            app.status_reason.as_deref(),
+            Some("Restarting in 1s (attempt 1)")
+        );
+
+        assert_eq!(apply_exit(&mut app, &mut state, &exited_with(1)), None);
+        assert!(matches!(app.status, AppStatus::Error));
+        assert_eq!(
+            app.status_reason.as_deref(),
+            Some("Gave up after 1 restart attempts")
+        );
+    }
+
+    #[test]
+    fn reconciles_apps_persisted_as_running() {
+        let pid = std::process::id();
+        let start_time = process::read_proc_stat(pid).unwrap().start_time;
+
+        let mut alive = app(AppStatus::Running);
+        alive.id = "alive".to_string();
+        alive.pid = Some(pid);
+        alive.start_time = Some(start_time);
+
+        // Same pid, but a different start time: the pid was reused
+        let mut reused = app(AppStatus::Running);
+        reused.id = "reused".to_string();
+        reused.pid = Some(pid);
+        reused.start_time = Some(start_time + 1);
+
+        let mut stopped = app(AppStatus::Stopped);
+        stopped.id = "stopped".to_string();
+
+        let mut apps: HashMap<String, TauriApp> = [alive, reused, stopped]
+            .into_iter()
+            .map(|app| (app.id.clone(), app))
+            .collect();
+        let adopted = reconcile_running_apps(&mut apps);
+
+        assert_eq!(adopted.keys().collect::<Vec<_>>(), vec!["alive"]);
+        assert_eq!(adopted["alive"].pid, pid);
+        assert!(matches!(apps["alive"].status, AppStatus::Running));
+        assert!(matches!(apps["reused"].status, AppStatus::Stopped));
+        assert_eq!(apps["reused"].pid, None);
+        assert!(matches!(apps["stopped"].status, AppStatus::Stopped));
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+// Rates need two readings; on the first call we wait this long between them
+const FIRST_SAMPLE_GAP: Duration = Duration::from_millis(250);
+
+// /proc/diskstats always counts in 512 byte sectors
+const SECTOR_BYTES: u64 = 512;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemSnapshot {
+    pub timestamp: String,
+    pub cpu: CpuUsage,
+    pub load: LoadAverage,
+    pub memory: MemoryUsage,
+    pub swap: SwapUsage,
+    pub disks: Vec<DiskUsage>,
+    pub disk_io: Vec<DiskIo>,
+    pub network: Vec<NetworkInterface>,
+    pub temperatures: Vec<Temperature>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CpuUsage {
+    pub total_percent: f64,
+    pub cores: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryUsage {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    pub used_bytes: u64,
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SwapUsage {
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskUsage {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    pub used_bytes: u64,
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskIo {
+    pub device: String,
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    pub rx_total_bytes: u64,
+    pub tx_total_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Temperature {
+    // hwmon chip name, e.g. "coretemp" or "amdgpu"
+    pub chip: String,
+    pub label: String,
+    pub celsius: f64,
+    pub critical_celsius: Option<f64>,
+}
+
+// Cumulative counters from the previous snapshot, rates are computed against
+// these
+#[derive(Debug, Clone, Default)]
+struct Counters {
+    // (busy, total) jiffies, first entry is the aggregate "cpu" line
+    cpu: Vec<(u64, u64)>,
+    // (bytes read, bytes written) per disk
+    disks: HashMap<String, (u64, u64)>,
+    // (bytes received, bytes sent) per interface
+    net: HashMap<String, (u64, u64)>,
+}
+
+#[derive(Default)]
+pub struct SystemMonitorState {
+    last: Option<(Counters, Instant)>,
+}
+
+pub type SystemMonitorRegistry = Mutex<SystemMonitorState>;
+
+fn read(path: impl AsRef<Path>) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+fn parse_cpu_times(stat: &str) -> Vec<(u64, u64)> {
+    stat.lines()
+        .filter(|line| line.starts_with("cpu"))
+        .map(|line| {
+            let values: Vec<u64> = line
+                .split_whitespace()
+                .skip(1)
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            // guest and guest_nice are already included in user and nice
+            let total: u64 = values.iter().take(8).sum();
+            let idle = values.get(3).copied().unwrap_or(0) + values.get(4).copied().unwrap_or(0);
+            (total - idle, total)
+        })
+        .collect()
+}
+
+fn parse_load_average(loadavg: &str) -> LoadAverage {
+    let mut values = loadavg.split_whitespace().map(|v| v.parse().unwrap_or(0.0));
+    LoadAverage {
+        one: values.next().unwrap_or(0.0),
+        five: values.next().unwrap_or(0.0),
+        fifteen: values.next().unwrap_or(0.0),
+    }
+}
+
+// Values of /proc/meminfo in bytes
+fn parse_meminfo(meminfo: &str) -> HashMap<String, u64> {
+    meminfo
+        .lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let kb: u64 = rest.split_whitespace().next()?.parse().ok()?;
+            Some((key.to_string(), kb * 1024))
+        })
+        .collect()
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total > 0 {
+        part as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    }
+}
+
+fn memory_usage(meminfo: &HashMap<String, u64>) -> (MemoryUsage, SwapUsage) {
+    let get = |key: &str| meminfo.get(key).copied().unwrap_or(0);
+
+    let total = get("MemTotal");
+    let available = get("MemAvailable");
+    let used = total.saturating_sub(available);
+    let swap_total = get("SwapTotal");
+    let swap_used = swap_total.saturating_sub(get("SwapFree"));
+
+    (
+        MemoryUsage {
+            total_bytes: total,
+            available_bytes: available,
+            used_bytes: used,
+            percent: percent(used, total),
+        },
+        SwapUsage {
+            total_bytes: swap_total,
+            used_bytes: swap_used,
+            percent: percent(swap_used, swap_total),
+        },
+    )
+}
+
+// Whole disks only: partitions would count the same I/O twice, and loop and
+// ram devices are noise
+fn parse_diskstats(diskstats: &str, is_disk: impl Fn(&str) -> bool) -> HashMap<String, (u64, u64)> {
+    diskstats
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let name = *fields.get(2)?;
+            if name.starts_with("loop") || name.starts_with("ram") || !is_disk(name) {
+                return None;
+            }
+            let read: u64 = fields.get(5)?.parse().ok()?;
+            let written: u64 = fields.get(9)?.parse().ok()?;
+            Some((name.to_string(), (read * SECTOR_BYTES, written * SECTOR_BYTES)))
+        })
+        .collect()
+}
+
+fn parse_net_dev(net_dev: &str) -> HashMap<String, (u64, u64)> {
+    net_dev
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, rest) = line.split_once(':')?;
+            let name = name.trim();
+            if name == "lo" {
+                return None;
+            }
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            let rx: u64 = fields.first()?.parse().ok()?;
+            let tx: u64 = fields.get(8)?.parse().ok()?;
+            Some((name.to_string(), (rx, tx)))
+        })
+        .collect()
+}
+
+// /proc/mounts escapes spaces and other odd characters as octal
+fn unescape_mount(field: &str) -> String {
+    let mut out = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let digits: String = chars.by_ref().take(3).collect();
+            if let Ok(code) = u8::from_str_radix(&digits, 8) {
+                out.push(code as char);
+                continue;
+            }
+            out.push(c);
+            out.push_str(&digits);
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn statvfs(mount_point: &str) -> Option<(u64, u64)> {
+    let path = CString::new(mount_point).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: path is NUL terminated and stat is a valid out pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    Some((stat.f_blocks as u64 * block, stat.f_bavail as u64 * block))
+}
+
+// Usage of every mounted block device, each device reported once even if it
+// is bind-mounted in several places
+fn disk_usage(mounts: &str) -> Vec<DiskUsage> {
+    let mut seen = Vec::new();
+    let mut disks = Vec::new();
+
+    for line in mounts.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (device, mount_point, fs_type) = match fields.as_slice() {
+            [device, mount_point, fs_type, ..] => (*device, unescape_mount(mount_point), *fs_type),
+            _ => continue,
+        };
+        if !device.starts_with("/dev/") || seen.contains(&device) || fs_type == "squashfs" {
+            continue;
+        }
+
+        if let Some((total, available)) = statvfs(&mount_point) {
+            seen.push(device);
+            let used = total.saturating_sub(available);
+            disks.push(DiskUsage {
+                device: device.to_string(),
+                mount_point,
+                fs_type: fs_type.to_string(),
+                total_bytes: total,
+                available_bytes: available,
+                used_bytes: used,
+                percent: percent(used, total),
+            });
+        }
+    }
+    disks
+}
+
+fn read_temperatures(hwmon_dir: &Path) -> Vec<Temperature> {
+    let mut chips: Vec<_> = match fs::read_dir(hwmon_dir) {
+        Ok(entries) => entries.flatten().map(|e| e.path()).collect(),
+        Err(_) => return Vec::new(),
+    };
+    chips.sort();
+
+    let mut temperatures = Vec::new();
+    for chip_dir in chips {
+        let chip = read(chip_dir.join("name")).trim().to_string();
+        let mut inputs: Vec<String> = match fs::read_dir(&chip_dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with("temp") && name.ends_with("_input"))
+                .collect(),
+            Err(_) => continue,
+        };
+        inputs.sort();
+
+        for input in inputs {
+            let sensor = input.trim_end_matches("_input");
+            let millidegrees = |file: String| read(chip_dir.join(file)).trim().parse::<f64>().ok();
+            let celsius = match millidegrees(input.clone()) {
+                Some(value) => value / 1000.0,
+                None => continue,
+            };
+            let label = read(chip_dir.join(format!("{}_label", sensor))).trim().to_string();
+
+            temperatures.push(Temperature {
+                chip: chip.clone(),
+                label: if label.is_empty() { sensor.to_string() } else { label },
+                celsius,
+                critical_celsius: millidegrees(format!("{}_crit", sensor)).map(|v| v / 1000.0),
+            });
+        }
+    }
+    temperatures
+}
+
+fn read_counters() -> Counters {
+    Counters {
+        cpu: parse_cpu_times(&read("/proc/stat")),
+        disks: parse_diskstats(&read("/proc/diskstats"), |name| {
+            Path::new("/sys/block").join(name).exists()
+        }),
+        net: parse_net_dev(&read("/proc/net/dev")),
+    }
+}
+
+fn cpu_percent((busy, total): (u64, u64), (prev_busy, prev_total): (u64, u64)) -> f64 {
+    percent(busy.saturating_sub(prev_busy), total.saturating_sub(prev_total))
+}
+
+fn rate(now: u64, before: u64, secs: f64) -> f64 {
+    if secs > 0.0 {
+        now.saturating_sub(before) as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+pub fn snapshot(state: &mut SystemMonitorState) -> SystemSnapshot {
+    let (prev, prev_at) = match state.last.take() {
+        Some(last) => last,
+        None => {
+            let at = Instant::now();
+            let baseline = read_counters();
+            thread::sleep(FIRST_SAMPLE_GAP);
+            (baseline, at)
+        }
+    };
+    let counters = read_counters();
+    let now = Instant::now();
+    let secs = now.duration_since(prev_at).as_secs_f64();
+
+    let cpu_usages: Vec<f64> = counters
+        .cpu
+        .iter()
+        .enumerate()
+        .map(|(i, times)| cpu_percent(*times, prev.cpu.get(i).copied().unwrap_or_default()))
+        .collect();
+
+    let mut disk_io: Vec<DiskIo> = counters
+        .disks
+        .iter()
+        .map(|(device, (read, written))| {
+            let (prev_read, prev_written) = prev.disks.get(device).copied().unwrap_or((*read, *written));
+            DiskIo {
+                device: device.clone(),
+                read_bytes_per_sec: rate(*read, prev_read, secs),
+                write_bytes_per_sec: rate(*written, prev_written, secs),
+            }
+        })
+        .collect();
+    disk_io.sort_by(|a, b| a.device.cmp(&b.device));
+
+    let mut network: Vec<NetworkInterface> = counters
+        .net
+        .iter()
+        .map(|(name, (rx, tx))| {
+            let (prev_rx, prev_tx) = prev.net.get(name).copied().unwrap_or((*rx, *tx));
+            NetworkInterface {
+                name: name.clone(),
+                rx_bytes_per_sec: rate(*rx, prev_rx, secs),
+                tx_bytes_per_sec: rate(*tx, prev_tx, secs),
+                rx_total_bytes: *rx,
+                tx_total_bytes: *tx,
+            }
+        })
+        .collect();
+    network.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let (memory, swap) = memory_usage(&parse_meminfo(&read("/proc/meminfo")));
+
+    state.last = Some((counters, now));
+
+    SystemSnapshot {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        cpu: CpuUsage {
+            total_percent: cpu_usages.first().copied().unwrap_or(0.0),
+            cores: cpu_usages.into_iter().skip(1).collect(),
+        },
+        load: parse_load_average(&read("/proc/loadavg")),
+        memory,
+        swap,
+        disks: disk_usage(&read("/proc/mounts")),
+        disk_io,
+        network,
+        temperatures: read_temperatures(Path::new("/sys/class/hwmon")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    #[test]
+    fn parses_cpu_lines() {
+        let stat = "cpu  100 0 50 800 50 0 0 0 0 0\n\
+                    cpu0 60 0 20 400 20 0 0 0 0 0\n\
+                    cpu1 40 0 30 400 30 0 0 0 0 0\n\
+                    intr 12345\n";
+        let times = parse_cpu_times(stat);
+        assert_eq!(times, vec![(150, 1000), (80, 500), (70, 500)]);
+        assert_eq!(cpu_percent((250, 1200), times[0]), 50.0);
+    }
+
+    #[test]
+    fn parses_load_and_memory() {
+        assert_eq!(
+            parse_load_average("0.52 1.04 2.50 1/234 5678\n"),
+            LoadAverage { one: 0.52, five: 1.04, fifteen: 2.5 }
+        );
+
+        let meminfo = parse_meminfo(
+            "MemTotal:       1000 kB\nMemAvailable:    250 kB\nSwapTotal:       400 kB\nSwapFree:        300 kB\n",
+        );
+        let (memory, swap) = memory_usage(&meminfo);
+        assert_eq!(memory.used_bytes, 750 * 1024);
+        assert_eq!(memory.percent, 75.0);
+        assert_eq!(swap.used_bytes, 100 * 1024);
+        assert_eq!(swap.percent, 25.0);
+    }
+
+    #[test]
+    fn parses_diskstats_and_net_dev() {
+        let diskstats = "   8       0 sda 100 0 2000 0 50 0 4000 0 0 0 0\n\
+                         8       1 sda1 100 0 2000 0 50 0 4000 0 0 0 0\n\
+                         7       0 loop0 1 0 8 0 0 0 0 0 0 0 0\n";
+        let disks = parse_diskstats(diskstats, |name| name == "sda" || name == "loop0");
+        assert_eq!(disks.len(), 1);
+        assert_eq!(disks["sda"], (2000 * 512, 4000 * 512));
+
+        let net_dev = "Inter-|   Receive                                                |  Transmit\n \
+                        face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n    \
+                        lo:    1000      10    0    0    0     0          0         0     1000      10    0    0    0     0       0          0\n  \
+                        eth0: 123456     100    0    0    0     0          0         0    65432      90    0    0    0     0       0          0\n";
+        let net = parse_net_dev(net_dev);
+        assert_eq!(net.len(), 1);
+        assert_eq!(net["eth0"], (123456, 65432));
+    }
+
+    #[test]
+    fn unescapes_mount_points() {
+        assert_eq!(unescape_mount("/mnt/my\\040disk"), "/mnt/my disk");
+        assert_eq!(unescape_mount("/"), "/");
+    }
+
+    #[test]
+    fn reads_hwmon_temperatures() {
+        let fx = Fixture::new("hwmon");
+        fx.write("hwmon0/name", "coretemp\n");
+        fx.write("hwmon0/temp1_input", "45000\n");
+        fx.write("hwmon0/temp1_label", "Package id 0\n");
+        fx.write("hwmon0/temp1_crit", "100000\n");
+        fx.write("hwmon0/temp2_input", "38500\n");
+
+        let temperatures = read_temperatures(&fx.dir);
+
+        assert_eq!(
+            temperatures,
+            vec![
+                Temperature {
+                    chip: "coretemp".to_string(),
+                    label: "Package id 0".to_string(),
+                    celsius: 45.0,
+                    critical_celsius: Some(100.0),
+                },
+                Temperature {
+                    chip: "coretemp".to_string(),
+                    label: "temp2".to_string(),
+                    celsius: 38.5,
+                    critical_celsius: None,
+                },
+            ]
+        );
+    }
+}
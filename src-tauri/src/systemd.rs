@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+
+use tauri::{AppHandle, Emitter, Manager};
+use zbus::blocking::{proxy::Builder, Connection, MessageIterator, Proxy};
+use zbus::message::Type as MessageType;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::MatchRule;
+
+use crate::error::HubError;
+use crate::services::{ServiceDefinition, ServiceScope, ServiceStatus};
+use crate::settings::SettingsState;
+
+const DESTINATION: &str = "org.freedesktop.systemd1";
+const MANAGER_PATH: &str = "/org/freedesktop/systemd1";
+const UNIT_PATH_PREFIX: &str = "/org/freedesktop/systemd1/unit/";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+const SERVICE_INTERFACE: &str = "org.freedesktop.systemd1.Service";
+
+// systemd reports unset accounting values as the maximum u64
+const UNSET: u64 = u64::MAX;
+
+pub const SERVICE_STATUS_EVENT: &str = "service-status-changed";
+
+// One connection per bus, opened on first use. A bus that can't be reached
+// (no session bus, systemd not running) is remembered as None so callers fall
+// back to systemctl without retrying every time.
+#[derive(Default)]
+pub struct SystemdBus {
+    connections: Mutex<HashMap<ServiceScope, Option<Connection>>>,
+}
+
+impl SystemdBus {
+    pub fn connection(&self, scope: ServiceScope) -> Option<Connection> {
+        let mut connections = self.connections.lock().ok()?;
+        connections
+            .entry(scope)
+            .or_insert_with(|| {
+                let result = match scope {
+                    ServiceScope::System => Connection::system(),
+                    ServiceScope::User => Connection::session(),
+                };
+                result
+                    .map_err(|e| eprintln!("Failed to connect to the {:?} bus: {}", scope, e))
+                    .ok()
+            })
+            .clone()
+    }
+}
+
+fn proxy<'a>(conn: &Connection, path: &'a str, interface: &'a str) -> Result<Proxy<'a>, zbus::Error> {
+    Builder::new(conn)
+        .destination(DESTINATION)?
+        .path(path)?
+        .interface(interface)?
+        .cache_properties(CacheProperties::No)
+        .build()
+}
+
+// Object path systemd gives a unit: every byte outside [A-Za-z0-9] (and a
+// leading digit) is written as _xx
+pub fn unit_object_path(unit: &str) -> String {
+    let mut path = String::from(UNIT_PATH_PREFIX);
+    if unit.is_empty() {
+        path.push('_');
+    }
+    for (i, b) in unit.bytes().enumerate() {
+        if b.is_ascii_alphabetic() || (i > 0 && b.is_ascii_digit()) {
+            path.push(b as char);
+        } else {
+            path.push_str(&format!("_{:02x}", b));
+        }
+    }
+    path
+}
+
+fn optional(value: u64) -> Option<u64> {
+    (value != UNSET).then_some(value)
+}
+
+// Timestamps are microseconds since the epoch, 0 when the event never happened
+pub fn timestamp_from_usec(usec: u64) -> Option<String> {
+    if usec == 0 || usec == UNSET {
+        return None;
+    }
+    chrono::DateTime::from_timestamp_micros(usec as i64).map(|t| t.to_rfc3339())
+}
+
+pub fn status(conn: &Connection, service: &ServiceDefinition) -> Result<ServiceStatus, HubError> {
+    let manager = proxy(conn, MANAGER_PATH, MANAGER_INTERFACE)?;
+    // LoadUnit works for units that aren't running (GetUnit doesn't)
+    let path: OwnedObjectPath = manager.call("LoadUnit", &(service.unit.as_str(),))?;
+
+    let unit = proxy(conn, path.as_str(), UNIT_INTERFACE)?;
+    let active_state: String = unit.get_property("ActiveState")?;
+    let unit_file_state: String = unit.get_property("UnitFileState")?;
+    let active_enter: u64 = unit.get_property("ActiveEnterTimestamp")?;
+
+    let mut status = ServiceStatus {
+        service: service.clone(),
+        load_state: unit.get_property("LoadState")?,
+        sub_state: unit.get_property("SubState")?,
+        active: active_state == "active",
+        enabled: crate::services::is_enabled(&unit_file_state),
+        active_state,
+        unit_file_state,
+        main_pid: None,
+        memory_bytes: None,
+        cpu_usage_nsec: None,
+        exit_status: None,
+        result: None,
+        active_since: timestamp_from_usec(active_enter),
+    };
+
+    // The rest only exists on .service units, and not for units that aren't installed
+    if service.unit.ends_with(".service") && status.load_state == "loaded" {
+        let svc = proxy(conn, path.as_str(), SERVICE_INTERFACE)?;
+        let main_pid: u32 = svc.get_property("MainPID")?;
+        let exec_main_code: i32 = svc.get_property("ExecMainCode")?;
+        status.main_pid = (main_pid != 0).then_some(main_pid);
+        status.memory_bytes = optional(svc.get_property("MemoryCurrent")?);
+        status.cpu_usage_nsec = optional(svc.get_property("CPUUsageNSec")?);
+        if exec_main_code != 0 {
+            status.exit_status = Some(svc.get_property("ExecMainStatus")?);
+        }
+        status.result = Some(svc.get_property("Result")?);
+    }
+    Ok(status)
+}
+
+// Follow PropertiesChanged on one bus and emit the fresh status of every
+// configured service whose unit changed
+fn watch_bus(app_handle: &AppHandle, scope: ServiceScope) -> Result<(), zbus::Error> {
+    let conn = match app_handle.state::<SystemdBus>().connection(scope) {
+        Some(conn) => conn,
+        None => return Ok(()),
+    };
+
+    // systemd only sends unit signals once somebody has subscribed
+    proxy(&conn, MANAGER_PATH, MANAGER_INTERFACE)?.call::<_, _, ()>("Subscribe", &())?;
+
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .sender(DESTINATION)?
+        .interface("org.freedesktop.DBus.Properties")?
+        .member("PropertiesChanged")?
+        .path_namespace("/org/freedesktop/systemd1/unit")?
+        .build();
+
+    for message in MessageIterator::for_match_rule(rule, &conn, Some(256))? {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("systemd signal error: {}", e);
+                continue;
+            }
+        };
+        let header = message.header();
+        let path = match header.path() {
+            Some(path) => path.as_str(),
+            None => continue,
+        };
+
+        // Services are re-read on every signal so edits to settings.json apply
+        let services: Vec<ServiceDefinition> = match app_handle.state::<SettingsState>().lock() {
+            Ok(settings) => settings
+                .services
+                .iter()
+                .filter(|s| s.scope == scope && unit_object_path(&s.unit) == path)
+                .cloned()
+                .collect(),
+            Err(_) => continue,
+        };
+        for service in services {
+            match status(&conn, &service) {
+                Ok(status) => {
+                    let _ = app_handle.emit(SERVICE_STATUS_EVENT, &status);
+                }
+                Err(e) => eprintln!("Failed to read status of {}: {}", service.unit, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn start_service_watcher(app_handle: &AppHandle) {
+    for scope in [ServiceScope::System, ServiceScope::User] {
+        let app_handle = app_handle.clone();
+        thread::spawn(move || {
+            if let Err(e) = watch_bus(&app_handle, scope) {
+                eprintln!("Stopped watching {:?} services: {}", scope, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_unit_names_like_systemd() {
+        assert_eq!(unit_object_path("docker.service"), "/org/freedesktop/systemd1/unit/docker_2eservice");
+        assert_eq!(
+            unit_object_path("open-webui.service"),
+            "/org/freedesktop/systemd1/unit/open_2dwebui_2eservice"
+        );
+        assert_eq!(unit_object_path("1password.service"), "/org/freedesktop/systemd1/unit/_31password_2eservice");
+        assert_eq!(unit_object_path("user@1000.service"), "/org/freedesktop/systemd1/unit/user_401000_2eservice");
+    }
+
+    #[test]
+    fn converts_systemd_timestamps() {
+        assert_eq!(timestamp_from_usec(0), None);
+        assert_eq!(timestamp_from_usec(UNSET), None);
+        assert_eq!(timestamp_from_usec(1_700_000_000_000_000).as_deref(), Some("2023-11-14T22:13:20+00:00"));
+    }
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_FIXTURE: AtomicU64 = AtomicU64::new(1);
+
+// A scratch directory for one test, removed again when the fixture is
+// dropped. Tests run as threads of one process, so the name also gets a
+// counter to keep two fixtures with the same name apart.
+pub struct Fixture {
+    pub dir: PathBuf,
+}
+
+impl Fixture {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "tauri-hub-{}-{}-{}",
+            name,
+            std::process::id(),
+            NEXT_FIXTURE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Canonical, so paths read back from /proc or the filesystem compare equal
+        Fixture { dir: fs::canonicalize(&dir).unwrap() }
+    }
+
+    // Write `content` to `path` inside the fixture, creating parent directories
+    pub fn write(&self, path: &str, content: &str) -> PathBuf {
+        let path = self.dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    pub fn write_with_mode(&self, path: &str, content: &str, mode: u32) -> PathBuf {
+        let path = self.write(path, content);
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,639 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{self, HubError};
+use crate::expand_home;
+use crate::stt::{SttDevice, SttEngine, SttSettings};
+
+// How long the HTTP engine waits for the server to accept the connection;
+// the transcription itself may take minutes
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A stretch of speech, in seconds from the start of the recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub text: String,
+    // Empty when the engine doesn't report timestamps
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: bool,
+    // Engine process currently running for the job
+    pid: Option<u32>,
+}
+
+// Shared between a transcription job and cancel_transcription
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation(Arc<Mutex<CancelState>>);
+
+fn kill(pid: u32) {
+    unsafe {
+        libc::kill(pid as i32, libc::SIGKILL);
+    }
+}
+
+impl Cancellation {
+    // Mark the job cancelled and kill its engine process, if any
+    pub fn cancel(&self) {
+        if let Ok(mut state) = self.0.lock() {
+            state.cancelled = true;
+            if let Some(pid) = state.pid.take() {
+                kill(pid);
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.lock().map(|state| state.cancelled).unwrap_or(false)
+    }
+
+    pub fn check(&self) -> Result<(), HubError> {
+        if self.is_cancelled() {
+            return Err(HubError::cancelled("Transcription cancelled"));
+        }
+        Ok(())
+    }
+
+    // Track the running engine process; one started after cancel() is killed straight away
+    fn attach(&self, pid: Option<u32>) {
+        if let Ok(mut state) = self.0.lock() {
+            match pid {
+                Some(pid) if state.cancelled => kill(pid),
+                _ => state.pid = pid,
+            }
+        }
+    }
+}
+
+// Turns a recorded WAV file into text. `on_segment` is called for every
+// segment as soon as the engine reports it.
+pub trait Transcriber: Send {
+    // Shown in errors and logs
+    fn name(&self) -> &'static str;
+    fn transcribe(
+        &self,
+        audio: &Path,
+        cancel: &Cancellation,
+        on_segment: &mut dyn FnMut(&Segment),
+    ) -> Result<Transcript, HubError>;
+}
+
+pub fn from_settings(settings: &SttSettings) -> Box<dyn Transcriber> {
+    match settings.engine {
+        SttEngine::WhisperCli => Box::new(WhisperCli { settings: settings.clone() }),
+        SttEngine::WhisperCpp => Box::new(WhisperCpp { settings: settings.clone() }),
+        SttEngine::Http => Box::new(HttpTranscriber { settings: settings.clone() }),
+    }
+}
+
+fn cuda_available() -> bool {
+    Path::new("/proc/driver/nvidia/version").exists()
+}
+
+// Whether the first attempt should use the GPU
+fn use_gpu(device: SttDevice, cuda: bool) -> bool {
+    match device {
+        SttDevice::Cpu => false,
+        SttDevice::Auto | SttDevice::Cuda if cuda => true,
+        SttDevice::Cuda => {
+            eprintln!("CUDA requested for transcription but no NVIDIA driver is loaded, using the CPU");
+            false
+        }
+        SttDevice::Auto => false,
+    }
+}
+
+// Run `attempt` on the GPU when wanted, retrying on the CPU if that fails.
+// The retry starts the recording over; segments up to where the failed
+// attempt got were already passed on, so they aren't reported twice.
+fn with_cpu_fallback<T>(
+    name: &str,
+    device: SttDevice,
+    on_segment: &mut dyn FnMut(&Segment),
+    attempt: impl FnMut(bool, &mut dyn FnMut(&Segment)) -> Result<T, HubError>,
+) -> Result<T, HubError> {
+    fallback_to_cpu(name, use_gpu(device, cuda_available()), on_segment, attempt)
+}
+
+fn fallback_to_cpu<T>(
+    name: &str,
+    gpu: bool,
+    on_segment: &mut dyn FnMut(&Segment),
+    mut attempt: impl FnMut(bool, &mut dyn FnMut(&Segment)) -> Result<T, HubError>,
+) -> Result<T, HubError> {
+    let mut reported_until: f64 = 0.0;
+    let mut track = |segment: &Segment| {
+        reported_until = reported_until.max(segment.end);
+        on_segment(segment);
+    };
+    match attempt(gpu, &mut track) {
+        Err(e) if gpu && !matches!(e, HubError::Cancelled { .. }) => {
+            eprintln!("{} failed on CUDA, retrying on the CPU: {}", name, e);
+            attempt(false, &mut |segment| {
+                if segment.end > reported_until {
+                    on_segment(segment);
+                }
+            })
+        }
+        result => result,
+    }
+}
+
+fn output_dir(settings: &SttSettings) -> Result<PathBuf, HubError> {
+    let dir = expand_home(&settings.output_dir);
+    fs::create_dir_all(&dir).map_err(|e| HubError::io("Failed to create output directory", e))?;
+    Ok(dir)
+}
+
+fn read_transcript(path: &Path) -> Result<String, HubError> {
+    fs::read_to_string(path).map_err(|e| HubError::io("Failed to read transcription", e))
+}
+
+// "[00:01.000 --> 00:04.500]  text" as printed by whisper and whisper.cpp
+// (which adds hours)
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    timestamp
+        .trim()
+        .split(':')
+        .try_fold(0.0, |total, part| part.parse::<f64>().ok().map(|n| total * 60.0 + n))
+}
+
+pub fn parse_segment_line(line: &str) -> Option<Segment> {
+    let (times, text) = line.trim_start().strip_prefix('[')?.split_once(']')?;
+    let (start, end) = times.split_once("-->")?;
+    Some(Segment {
+        start: parse_timestamp(start)?,
+        end: parse_timestamp(end)?,
+        text: text.trim().to_string(),
+    })
+}
+
+// Run an engine process, passing each segment it prints on stdout to
+// `on_segment`. The process can be killed through `cancel`.
+fn run_streaming(
+    cmd: &mut Command,
+    what: &str,
+    cancel: &Cancellation,
+    on_segment: &mut dyn FnMut(&Segment),
+) -> Result<Vec<Segment>, HubError> {
+    cancel.check()?;
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| error::spawn_error(&program, what, e))?;
+    cancel.attach(Some(child.id()));
+
+    // Drained on its own thread so progress bars can't fill the pipe
+    let stderr = child.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        })
+    });
+
+    let mut segments = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(segment) = parse_segment_line(&line) {
+                on_segment(&segment);
+                segments.push(segment);
+            }
+        }
+    }
+
+    let status = child.wait();
+    cancel.attach(None);
+    let stderr = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+    cancel.check()?;
+
+    let status = status.map_err(|e| HubError::io(&format!("Failed to {}", what), e))?;
+    error::check(cmd, Output { status, stdout: Vec::new(), stderr }, what)?;
+    Ok(segments)
+}
+
+fn file_stem(audio: &Path) -> Result<&std::ffi::OsStr, HubError> {
+    audio
+        .file_stem()
+        .ok_or_else(|| HubError::parse(format!("Invalid audio file name {:?}", audio)))
+}
+
+// The Python openai-whisper CLI, which writes <output_dir>/<stem>.txt
+pub struct WhisperCli {
+    settings: SttSettings,
+}
+
+impl WhisperCli {
+    fn args(&self, audio: &Path, output_dir: &Path, gpu: bool) -> Vec<String> {
+        let mut args = vec![
+            audio.to_string_lossy().into_owned(),
+            "--model".to_string(),
+            self.settings.model.clone(),
+            "--device".to_string(),
+            if gpu { "cuda" } else { "cpu" }.to_string(),
+            "--output_format".to_string(),
+            "txt".to_string(),
+            "--output_dir".to_string(),
+            output_dir.to_string_lossy().into_owned(),
+        ];
+        if let Some(language) = &self.settings.language {
+            args.push("--language".to_string());
+            args.push(language.clone());
+        }
+        args
+    }
+}
+
+impl Transcriber for WhisperCli {
+    fn name(&self) -> &'static str {
+        "Whisper"
+    }
+
+    fn transcribe(
+        &self,
+        audio: &Path,
+        cancel: &Cancellation,
+        on_segment: &mut dyn FnMut(&Segment),
+    ) -> Result<Transcript, HubError> {
+        let output_dir = output_dir(&self.settings)?;
+        let binary = expand_home(Path::new(&self.settings.binary));
+        let segments = with_cpu_fallback(self.name(), self.settings.device, on_segment, |gpu, on_segment| {
+            let mut cmd = Command::new(&binary);
+            // Segments are printed as they're decoded, but only reach the
+            // pipe in time if Python doesn't buffer them
+            cmd.args(self.args(audio, &output_dir, gpu)).env("PYTHONUNBUFFERED", "1");
+            let what = format!("run Whisper on {}", if gpu { "cuda" } else { "cpu" });
+            run_streaming(&mut cmd, &what, cancel, on_segment)
+        })?;
+        Ok(Transcript {
+            text: read_transcript(&output_dir.join(file_stem(audio)?).with_extension("txt"))?,
+            segments,
+        })
+    }
+}
+
+// whisper.cpp's `whisper-cli` (called `main` in older builds). `model` is the
+// path of a ggml model file and the audio has to be 16 kHz.
+pub struct WhisperCpp {
+    settings: SttSettings,
+}
+
+impl WhisperCpp {
+    fn args(&self, audio: &Path, output_base: &Path, gpu: bool) -> Vec<String> {
+        let mut args = vec![
+            "--model".to_string(),
+            expand_home(Path::new(&self.settings.model)).to_string_lossy().into_owned(),
+            "--file".to_string(),
+            audio.to_string_lossy().into_owned(),
+            "--output-txt".to_string(),
+            "--output-file".to_string(),
+            output_base.to_string_lossy().into_owned(),
+            "--no-prints".to_string(),
+        ];
+        if let Some(language) = &self.settings.language {
+            args.push("--language".to_string());
+            args.push(language.clone());
+        }
+        if !gpu {
+            args.push("--no-gpu".to_string());
+        }
+        args
+    }
+}
+
+impl Transcriber for WhisperCpp {
+    fn name(&self) -> &'static str {
+        "whisper.cpp"
+    }
+
+    fn transcribe(
+        &self,
+        audio: &Path,
+        cancel: &Cancellation,
+        on_segment: &mut dyn FnMut(&Segment),
+    ) -> Result<Transcript, HubError> {
+        // --output-file takes the path without the .txt it appends
+        let output_base = output_dir(&self.settings)?.join(file_stem(audio)?);
+        let binary = expand_home(Path::new(&self.settings.binary));
+        let segments = with_cpu_fallback(self.name(), self.settings.device, on_segment, |gpu, on_segment| {
+            let mut cmd = Command::new(&binary);
+            cmd.args(self.args(audio, &output_base, gpu));
+            run_streaming(&mut cmd, "run whisper.cpp", cancel, on_segment)
+        })?;
+        Ok(Transcript {
+            text: read_transcript(&output_base.with_extension("txt"))?,
+            segments,
+        })
+    }
+}
+
+// An OpenAI-compatible server (faster-whisper-server, LocalAI, ...) reached
+// at <endpoint>/v1/audio/transcriptions. Segments only arrive with the full
+// response, and a cancelled request is abandoned rather than aborted.
+pub struct HttpTranscriber {
+    settings: SttSettings,
+}
+
+impl HttpTranscriber {
+    fn url(&self) -> String {
+        format!("{}/v1/audio/transcriptions", self.settings.endpoint.trim_end_matches('/'))
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("model", self.settings.model.clone()),
+            ("response_format", "verbose_json".to_string()),
+        ];
+        if let Some(language) = &self.settings.language {
+            fields.push(("language", language.clone()));
+        }
+        fields
+    }
+}
+
+// multipart/form-data with plain text fields followed by the audio file
+fn multipart_body(boundary: &str, fields: &[(&str, String)], file_name: &str, audio: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value)
+                .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: audio/wav\r\n\r\n",
+            boundary,
+            file_name.replace('"', "")
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(audio);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+// verbose_json has the text plus segments; servers that only do plain json
+// leave the segments out
+fn parse_response(body: &str) -> Result<Transcript, HubError> {
+    let response: Value = serde_json::from_str(body)
+        .map_err(|e| HubError::parse(format!("Failed to parse transcription response: {}", e)))?;
+    let text = response
+        .get("text")
+        .and_then(Value::as_str)
+        .ok_or_else(|| HubError::parse("Transcription response has no text"))?;
+
+    let segments = response
+        .get("segments")
+        .and_then(Value::as_array)
+        .map(|segments| {
+            segments
+                .iter()
+                .filter_map(|s| {
+                    Some(Segment {
+                        start: s.get("start")?.as_f64()?,
+                        end: s.get("end")?.as_f64()?,
+                        text: s.get("text")?.as_str()?.trim().to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Transcript { text: text.to_string(), segments })
+}
+
+impl Transcriber for HttpTranscriber {
+    fn name(&self) -> &'static str {
+        "transcription server"
+    }
+
+    fn transcribe(
+        &self,
+        audio: &Path,
+        cancel: &Cancellation,
+        on_segment: &mut dyn FnMut(&Segment),
+    ) -> Result<Transcript, HubError> {
+        cancel.check()?;
+        let bytes = fs::read(audio).map_err(|e| HubError::io("Failed to read recording", e))?;
+        let boundary = format!("tauri-hub-{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        let file_name = audio.file_name().unwrap_or_default().to_string_lossy();
+        let body = multipart_body(&boundary, &self.fields(), &file_name, &bytes);
+
+        let url = self.url();
+        let mut request = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT)
+            .build()
+            .post(&url)
+            .set("Content-Type", &format!("multipart/form-data; boundary={}", boundary));
+        if let Some(api_key) = &self.settings.api_key {
+            request = request.set("Authorization", &format!("Bearer {}", api_key));
+        }
+
+        let response = match request.send_bytes(&body) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, response)) => {
+                let detail = response.into_string().unwrap_or_default();
+                return Err(HubError::Io {
+                    message: format!("{} returned HTTP {}: {}", url, status, detail.trim()),
+                });
+            }
+            Err(e) => {
+                return Err(HubError::Io {
+                    message: format!("Failed to reach {}: {}", url, e),
+                })
+            }
+        };
+        let text = response
+            .into_string()
+            .map_err(|e| HubError::io("Failed to read transcription response", e))?;
+        cancel.check()?;
+
+        let transcript = parse_response(&text)?;
+        for segment in &transcript.segments {
+            on_segment(segment);
+        }
+        Ok(transcript)
+    }
+}
+
+// Returns canned text without touching the audio
+#[cfg(test)]
+pub struct MockTranscriber {
+    pub text: String,
+}
+
+#[cfg(test)]
+impl Transcriber for MockTranscriber {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn transcribe(
+        &self,
+        _audio: &Path,
+        cancel: &Cancellation,
+        on_segment: &mut dyn FnMut(&Segment),
+    ) -> Result<Transcript, HubError> {
+        cancel.check()?;
+        let segment = Segment { start: 0.0, end: 1.0, text: self.text.clone() };
+        on_segment(&segment);
+        Ok(Transcript { text: self.text.clone(), segments: vec![segment] })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    #[test]
+    fn runs_the_whisper_cli() {
+        let fx = Fixture::new("transcriber-whisper");
+        let dir = &fx.dir;
+        let whisper = fx.write_with_mode(
+            "fake-whisper",
+            "#!/bin/sh\naudio=$1; shift\nwhile [ $# -gt 0 ]; do [ \"$1\" = --output_dir ] && out=$2; shift; done\n\
+             echo \"[00:00.000 --> 00:01.500]  hello\"\necho \"[00:01.500 --> 00:02.000]  there\"\n\
+             echo \"hello there\" > \"$out/$(basename \"$audio\" .wav).txt\"\n",
+            0o755,
+        );
+
+        let settings = SttSettings {
+            binary: whisper.to_string_lossy().into_owned(),
+            device: SttDevice::Cpu,
+            output_dir: dir.join("out"),
+            ..SttSettings::default()
+        };
+        let mut seen = Vec::new();
+        let transcript = from_settings(&settings)
+            .transcribe(&dir.join("recording_1.wav"), &Cancellation::default(), &mut |s| seen.push(s.text.clone()))
+            .unwrap();
+        assert_eq!(transcript.text, "hello there\n");
+        assert_eq!(seen, ["hello", "there"]);
+        assert_eq!(transcript.segments[0], Segment { start: 0.0, end: 1.5, text: "hello".to_string() });
+    }
+
+    #[test]
+    fn cancelling_kills_the_engine() {
+        let fx = Fixture::new("transcriber-cancel");
+        let whisper = fx.write_with_mode(
+            "slow-whisper",
+            "#!/bin/sh\necho \"[00:00.000 --> 00:01.000]  first\"\nexec sleep 30\n",
+            0o755,
+        );
+
+        let cancel = Cancellation::default();
+        let mut cmd = Command::new(&whisper);
+        let canceller = cancel.clone();
+        let result = run_streaming(&mut cmd, "run Whisper", &cancel, &mut |_| canceller.cancel());
+        assert!(matches!(result, Err(HubError::Cancelled { .. })));
+    }
+
+    #[test]
+    fn cpu_retry_does_not_repeat_segments() {
+        let segment = |start: f64, end: f64| Segment { start, end, text: format!("{}-{}", start, end) };
+        let mut seen = Vec::new();
+        let result = fallback_to_cpu("test", true, &mut |s| seen.push(s.end), |gpu, on_segment| {
+            on_segment(&segment(0.0, 1.0));
+            on_segment(&segment(1.0, 2.0));
+            if gpu {
+                return Err(HubError::from("CUDA out of memory"));
+            }
+            on_segment(&segment(2.0, 3.0));
+            Ok(())
+        });
+        assert!(result.is_ok());
+        assert_eq!(seen, vec![1.0, 2.0, 3.0]);
+
+        // Cancelling isn't a GPU problem, so there is no retry
+        let mut attempts = 0;
+        let result = fallback_to_cpu("test", true, &mut |_| {}, |_: bool, _: &mut dyn FnMut(&Segment)| -> Result<(), HubError> {
+            attempts += 1;
+            Err(HubError::cancelled("Transcription cancelled"))
+        });
+        assert!(matches!(result, Err(HubError::Cancelled { .. })));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn parses_segment_lines() {
+        assert_eq!(
+            parse_segment_line("[00:00:01.240 --> 00:00:03.000]   Hi."),
+            Some(Segment { start: 1.24, end: 3.0, text: "Hi.".to_string() })
+        );
+        assert_eq!(parse_segment_line("[01:02.500 --> 01:05.000] x").map(|s| s.start), Some(62.5));
+        assert_eq!(parse_segment_line("Detecting language using up to the first 30 seconds."), None);
+    }
+
+    #[test]
+    fn builds_engine_arguments() {
+        assert!(use_gpu(SttDevice::Auto, true));
+        assert!(!use_gpu(SttDevice::Auto, false));
+        assert!(!use_gpu(SttDevice::Cuda, false));
+        assert!(!use_gpu(SttDevice::Cpu, true));
+
+        let settings = SttSettings {
+            model: "~/models/ggml-small.bin".to_string(),
+            language: Some("de".to_string()),
+            ..SttSettings::default()
+        };
+        let whisper = WhisperCli { settings: settings.clone() };
+        assert_eq!(
+            whisper.args(Path::new("/rec/a.wav"), Path::new("/out"), false)[..5],
+            ["/rec/a.wav", "--model", "~/models/ggml-small.bin", "--device", "cpu"]
+        );
+
+        let cpp = WhisperCpp { settings };
+        let args = cpp.args(Path::new("/rec/a.wav"), Path::new("/out/a"), false);
+        assert!(!args[1].starts_with('~'));
+        assert_eq!(
+            args[2..],
+            ["--file", "/rec/a.wav", "--output-txt", "--output-file", "/out/a", "--no-prints", "--language", "de", "--no-gpu"]
+        );
+    }
+
+    #[test]
+    fn speaks_the_openai_transcription_api() {
+        let http = HttpTranscriber {
+            settings: SttSettings {
+                endpoint: "http://localhost:8000/".to_string(),
+                ..SttSettings::default()
+            },
+        };
+        assert_eq!(http.url(), "http://localhost:8000/v1/audio/transcriptions");
+
+        let body = multipart_body("b", &http.fields(), "a.wav", b"RIFF");
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "--b\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nsmall\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\nverbose_json\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\nContent-Type: audio/wav\r\n\r\n\
+             RIFF\r\n--b--\r\n"
+        );
+
+        assert_eq!(parse_response(r#"{"text":" Hello."}"#).unwrap().text, " Hello.");
+        let transcript =
+            parse_response(r#"{"text":"Hi there","segments":[{"id":0,"start":0.0,"end":1.2,"text":" Hi there"}]}"#).unwrap();
+        assert_eq!(transcript.segments, vec![Segment { start: 0.0, end: 1.2, text: "Hi there".to_string() }]);
+        assert!(matches!(parse_response(r#"{"error":"nope"}"#), Err(HubError::Parse { .. })));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::error::HubError;
+use crate::stt::{self, SttSettings};
+use crate::transcriber::{Cancellation, Segment, Transcript};
+use crate::transcripts::{self, TranscriptArchive, TranscriptRecord};
+
+pub const TRANSCRIPTION_PROGRESS_EVENT: &str = "transcription-progress";
+pub const TRANSCRIPTION_FINISHED_EVENT: &str = "transcription-finished";
+
+// Size of the canonical header of a 16-bit PCM WAV file
+const WAV_HEADER_LEN: u64 = 44;
+
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+
+// Running jobs, keyed by job ID
+pub type TranscriptionJobs = Mutex<HashMap<String, Cancellation>>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionProgress {
+    pub job_id: String,
+    // Share of the recording transcribed so far, 0.0 to 1.0; None when the
+    // length of the recording isn't known
+    pub progress: Option<f64>,
+    pub segment: Segment,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TranscriptionOutcome {
+    Completed {
+        // None if it couldn't be saved to the archive
+        #[serde(rename = "transcriptId")]
+        transcript_id: Option<String>,
+        text: String,
+        segments: Vec<Segment>,
+    },
+    Failed { error: HubError },
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptionFinished {
+    pub job_id: String,
+    #[serde(flatten)]
+    pub outcome: TranscriptionOutcome,
+}
+
+// Length in seconds of a 16-bit PCM WAV file, from the byte rate in its header
+// and the file size, so it also works for recordings whose header sizes were
+// never filled in
+pub fn wav_duration(path: &Path) -> Option<f64> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut header = [0u8; WAV_HEADER_LEN as usize];
+    file.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return None;
+    }
+    let byte_rate = u32::from_le_bytes(header[28..32].try_into().ok()?);
+    if byte_rate == 0 {
+        return None;
+    }
+    let len = file.metadata().ok()?.len();
+    Some((len - WAV_HEADER_LEN) as f64 / byte_rate as f64)
+}
+
+fn progress(segment: &Segment, duration: Option<f64>) -> Option<f64> {
+    duration
+        .filter(|&d| d > 0.0)
+        .map(|d| (segment.end / d).clamp(0.0, 1.0))
+}
+
+// Store a finished transcription in the archive and return its ID
+fn archive(
+    app_handle: &AppHandle,
+    settings: &SttSettings,
+    audio: &Path,
+    duration: Option<f64>,
+    transcript: &Transcript,
+) -> Result<String, HubError> {
+    let record = TranscriptRecord {
+        id: String::new(),
+        audio_path: settings.keep_audio.then(|| audio.to_path_buf()),
+        text: transcript.text.clone(),
+        segments: transcript.segments.clone(),
+        engine: settings.engine,
+        model: settings.model.clone(),
+        language: settings.language.clone(),
+        duration_secs: duration,
+        created_at: chrono::Local::now().to_rfc3339(),
+    };
+    let archive = app_handle.state::<TranscriptArchive>();
+    let mut archive = archive.lock()?;
+    let id = archive.add(record);
+    transcripts::save_archive(app_handle, &archive)?;
+    Ok(id)
+}
+
+// Transcribe `audio` on a background thread and return the job ID right away.
+// Segments are emitted as TRANSCRIPTION_PROGRESS_EVENT while the engine runs.
+// A successful transcript is archived first; `finish` then sees the result
+// before it goes out as TRANSCRIPTION_FINISHED_EVENT.
+pub fn start<F>(app_handle: &AppHandle, settings: SttSettings, audio: PathBuf, finish: F) -> Result<String, HubError>
+where
+    F: FnOnce(&AppHandle, Result<&Transcript, &HubError>) + Send + 'static,
+{
+    let job_id = format!("transcription-{}", NEXT_JOB.fetch_add(1, Ordering::Relaxed));
+    let cancel = Cancellation::default();
+    app_handle
+        .state::<TranscriptionJobs>()
+        .lock()?
+        .insert(job_id.clone(), cancel.clone());
+
+    let app_handle = app_handle.clone();
+    let id = job_id.clone();
+    thread::spawn(move || {
+        let duration = wav_duration(&audio);
+        let mut on_segment = |segment: &Segment| {
+            let event = TranscriptionProgress {
+                job_id: id.clone(),
+                progress: progress(segment, duration),
+                segment: segment.clone(),
+            };
+            let _ = app_handle.emit(TRANSCRIPTION_PROGRESS_EVENT, &event);
+        };
+        let result = stt::transcribe(&settings, &audio, &cancel, &mut on_segment).map(|transcript| {
+            let transcript_id = archive(&app_handle, &settings, &audio, duration, &transcript)
+                .map_err(|e| eprintln!("Failed to archive transcript: {}", e))
+                .ok();
+            (transcript_id, transcript)
+        });
+        finish(&app_handle, result.as_ref().map(|(_, transcript)| transcript));
+
+        if let Ok(mut jobs) = app_handle.state::<TranscriptionJobs>().lock() {
+            jobs.remove(&id);
+        }
+        let outcome = match result {
+            Ok((transcript_id, transcript)) => TranscriptionOutcome::Completed {
+                transcript_id,
+                text: transcript.text,
+                segments: transcript.segments,
+            },
+            Err(HubError::Cancelled { .. }) => TranscriptionOutcome::Cancelled,
+            Err(error) => TranscriptionOutcome::Failed { error },
+        };
+        let _ = app_handle.emit(TRANSCRIPTION_FINISHED_EVENT, &TranscriptionFinished { job_id: id, outcome });
+    });
+    Ok(job_id)
+}
+
+pub fn cancel(app_handle: &AppHandle, job_id: &str) -> Result<(), HubError> {
+    let jobs = app_handle.state::<TranscriptionJobs>();
+    let jobs = jobs.lock()?;
+    let cancel = jobs
+        .get(job_id)
+        .ok_or_else(|| HubError::not_found(format!("No transcription job '{}'", job_id)))?;
+    cancel.cancel();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    #[test]
+    fn measures_recordings_from_their_size() {
+        let fx = Fixture::new("transcription");
+        let path = fx.dir.join("recording.wav");
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF\xff\xff\xff\x7fWAVEfmt ");
+        wav.extend_from_slice(&[16, 0, 0, 0, 1, 0, 1, 0]);
+        wav.extend_from_slice(&16_000u32.to_le_bytes());
+        wav.extend_from_slice(&32_000u32.to_le_bytes());
+        wav.extend_from_slice(&[2, 0, 16, 0]);
+        wav.extend_from_slice(b"data\xff\xff\xff\x7f");
+        wav.resize(wav.len() + 48_000, 0);
+        fs::write(&path, &wav).unwrap();
+
+        let duration = wav_duration(&path);
+        assert_eq!(duration, Some(1.5));
+        let segment = Segment { start: 0.0, end: 0.75, text: String::new() };
+        assert_eq!(progress(&segment, duration), Some(0.5));
+        assert_eq!(progress(&segment, None), None);
+
+        fs::write(&path, "not a wav file at all, just some text padding it out").unwrap();
+        assert_eq!(wav_duration(&path), None);
+    }
+}
@@ -0,0 +1,208 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::error::HubError;
+use crate::get_app_data_dir;
+use crate::stt::SttEngine;
+use crate::transcriber::Segment;
+
+// Every finished transcription, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptRecord {
+    pub id: String,
+    // None once the recording was deleted (keep_audio off)
+    pub audio_path: Option<PathBuf>,
+    pub text: String,
+    pub segments: Vec<Segment>,
+    pub engine: SttEngine,
+    pub model: String,
+    pub language: Option<String>,
+    // Length of the recording in seconds
+    pub duration_secs: Option<f64>,
+    // RFC 3339
+    pub created_at: String,
+}
+
+// What list_transcripts and search_transcripts return: the record without
+// its segments
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptSummary {
+    pub id: String,
+    pub audio_path: Option<PathBuf>,
+    // False when the recording is gone and can't be re-transcribed
+    pub audio_available: bool,
+    pub text: String,
+    pub engine: SttEngine,
+    pub model: String,
+    pub duration_secs: Option<f64>,
+    pub created_at: String,
+}
+
+impl From<&TranscriptRecord> for TranscriptSummary {
+    fn from(record: &TranscriptRecord) -> Self {
+        TranscriptSummary {
+            id: record.id.clone(),
+            audio_path: record.audio_path.clone(),
+            audio_available: record.audio_path.as_deref().is_some_and(Path::is_file),
+            text: record.text.clone(),
+            engine: record.engine,
+            model: record.model.clone(),
+            duration_secs: record.duration_secs,
+            created_at: record.created_at.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TranscriptIndex {
+    transcripts: Vec<TranscriptRecord>,
+}
+
+pub type TranscriptArchive = Mutex<TranscriptIndex>;
+
+impl TranscriptIndex {
+    // Store a record, giving it an ID derived from its creation time
+    pub fn add(&mut self, mut record: TranscriptRecord) -> String {
+        let base = chrono::DateTime::parse_from_rfc3339(&record.created_at)
+            .map(|t| t.format("%Y%m%d-%H%M%S").to_string())
+            .unwrap_or_else(|_| "transcript".to_string());
+        let mut id = base.clone();
+        let mut n = 1;
+        while self.get(&id).is_some() {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        record.id = id.clone();
+        self.transcripts.push(record);
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<&TranscriptRecord> {
+        self.transcripts.iter().find(|t| t.id == id)
+    }
+
+    // Newest first
+    pub fn list(&self) -> Vec<TranscriptSummary> {
+        self.transcripts.iter().rev().map(TranscriptSummary::from).collect()
+    }
+
+    // Transcripts containing every word of `query`, ignoring case, newest first
+    pub fn search(&self, query: &str) -> Vec<TranscriptSummary> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        self.transcripts
+            .iter()
+            .rev()
+            .filter(|t| {
+                let text = t.text.to_lowercase();
+                words.iter().all(|w| text.contains(w.as_str()))
+            })
+            .map(TranscriptSummary::from)
+            .collect()
+    }
+
+    // Remove a record. Returns its recording when no other transcript of the
+    // same audio is left, so the caller can delete it if asked to.
+    pub fn remove(&mut self, id: &str) -> Result<Option<PathBuf>, HubError> {
+        let index = self
+            .transcripts
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or_else(|| HubError::not_found(format!("Transcript '{}' not found", id)))?;
+        let record = self.transcripts.remove(index);
+        Ok(record
+            .audio_path
+            .filter(|path| !self.transcripts.iter().any(|t| t.audio_path.as_ref() == Some(path))))
+    }
+}
+
+fn get_archive_path(app_handle: &AppHandle) -> Result<PathBuf, HubError> {
+    Ok(get_app_data_dir(app_handle)?.join("transcripts.json"))
+}
+
+pub fn load_archive(app_handle: &AppHandle) -> Result<TranscriptIndex, HubError> {
+    let path = get_archive_path(app_handle)?;
+    if !path.exists() {
+        return Ok(TranscriptIndex::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| HubError::io("Failed to read transcripts", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| HubError::parse(format!("Failed to parse transcripts: {}", e)))
+}
+
+pub fn save_archive(app_handle: &AppHandle, index: &TranscriptIndex) -> Result<(), HubError> {
+    let path = get_archive_path(app_handle)?;
+    let content = serde_json::to_string_pretty(index)
+        .map_err(|e| format!("Failed to serialize transcripts: {}", e))?;
+
+    let tmp_path = path.with_extension("json.tmp");
+    let write_tmp = || -> io::Result<()> {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)
+    };
+    write_tmp().map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        HubError::io("Failed to write transcripts", e)
+    })
+}
+
+pub fn delete_audio(path: &Path) -> Result<(), HubError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(HubError::io("Failed to delete recording", e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(text: &str, audio: &str, created_at: &str) -> TranscriptRecord {
+        TranscriptRecord {
+            id: String::new(),
+            audio_path: Some(PathBuf::from(audio)),
+            text: text.to_string(),
+            segments: Vec::new(),
+            engine: SttEngine::WhisperCli,
+            model: "small".to_string(),
+            language: None,
+            duration_secs: Some(3.0),
+            created_at: created_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn searches_newest_first() {
+        let mut index = TranscriptIndex::default();
+        let first = index.add(record("Buy milk and eggs", "/rec/a.wav", "2024-05-01T10:00:00+00:00"));
+        let second = index.add(record("Call the bank about the MILK budget", "/rec/b.wav", "2024-05-01T10:00:00+00:00"));
+        index.add(record("Nothing relevant", "/rec/c.wav", "2024-05-02T09:30:00+00:00"));
+        assert_eq!(first, "20240501-100000");
+        assert_eq!(second, "20240501-100000-2");
+
+        let ids = |summaries: Vec<TranscriptSummary>| summaries.into_iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(ids(index.search("milk")), vec![second.clone(), first.clone()]);
+        assert_eq!(ids(index.search("milk  EGGS")), vec![first.clone()]);
+        assert_eq!(index.list().len(), 3);
+        assert_eq!(index.list()[0].id, "20240502-093000");
+    }
+
+    #[test]
+    fn keeps_audio_shared_with_another_transcript() {
+        let mut index = TranscriptIndex::default();
+        let small = index.add(record("hello", "/rec/a.wav", "2024-05-01T10:00:00+00:00"));
+        let medium = index.add(record("Hello.", "/rec/a.wav", "2024-05-01T10:05:00+00:00"));
+
+        assert_eq!(index.remove(&small).unwrap(), None);
+        assert_eq!(index.remove(&medium).unwrap(), Some(PathBuf::from("/rec/a.wav")));
+        assert!(matches!(index.remove(&medium), Err(HubError::NotFound { .. })));
+    }
+}
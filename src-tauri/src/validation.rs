@@ -0,0 +1,449 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::profiles;
+use crate::{expand_home, TauriApp};
+
+// Emoji and short labels are shown as-is; anything longer has to be an image file
+const MAX_INLINE_ICON_CHARS: usize = 8;
+const ICON_EXTENSIONS: [&str; 6] = ["png", "svg", "ico", "jpg", "jpeg", "webp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldErrorCode {
+    Empty,
+    NotFound,
+    InvalidId,
+    IdCollision,
+    PathNotFound,
+    NotADirectory,
+    ExecutableNotFound,
+    NotExecutable,
+    InvalidIcon,
+    DuplicateProfile,
+    UnknownProfile,
+    InvalidValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    // Field path as the frontend knows it, e.g. "path" or "profiles[1].executable"
+    pub field: String,
+    pub code: FieldErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationMode {
+    // A new app: its id must not be taken yet
+    Register,
+    // An intentional edit of an existing app: its id must already exist
+    Update,
+}
+
+pub fn error(field: impl Into<String>, code: FieldErrorCode, message: impl Into<String>) -> FieldError {
+    FieldError {
+        field: field.into(),
+        code,
+        message: message.into(),
+    }
+}
+
+// Ids end up in log file names and metric names, so keep them to characters
+// that are safe in both
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn find_on_path(name: &str, path_var: &OsStr) -> Option<PathBuf> {
+    std::env::split_paths(path_var)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn check_executable(
+    field: &str,
+    executable: &str,
+    workdir: &Path,
+    path_var: &OsStr,
+) -> Option<FieldError> {
+    if executable.trim().is_empty() {
+        return Some(error(field, FieldErrorCode::Empty, "Executable is required"));
+    }
+
+    // Bare names are looked up on PATH, anything with a path component is
+    // resolved the same way launch_app does it
+    let candidate = profiles::resolve_executable(workdir, executable);
+    let resolved = if candidate.is_relative() && candidate.components().count() == 1 {
+        match find_on_path(executable, path_var) {
+            Some(found) => found,
+            None => {
+                return Some(error(
+                    field,
+                    FieldErrorCode::ExecutableNotFound,
+                    format!("Executable '{}' was not found on PATH", executable),
+                ))
+            }
+        }
+    } else {
+        candidate
+    };
+
+    if !resolved.exists() {
+        return Some(error(
+            field,
+            FieldErrorCode::ExecutableNotFound,
+            format!("Executable {:?} does not exist", resolved),
+        ));
+    }
+    if !is_executable(&resolved) {
+        return Some(error(
+            field,
+            FieldErrorCode::NotExecutable,
+            format!("{:?} is not an executable file", resolved),
+        ));
+    }
+    None
+}
+
+fn check_icon(icon: &str, app_dir: &Path) -> Option<FieldError> {
+    let looks_like_path = icon.contains('/')
+        || Path::new(icon)
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|ext| ICON_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+
+    if !looks_like_path {
+        if icon.chars().count() > MAX_INLINE_ICON_CHARS {
+            return Some(error(
+                "icon",
+                FieldErrorCode::InvalidIcon,
+                "Icon must be an emoji, a short label or a path to an image file",
+            ));
+        }
+        return None;
+    }
+
+    let path = app_dir.join(expand_home(Path::new(icon)));
+    let is_image = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ICON_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+    if !is_image {
+        return Some(error(
+            "icon",
+            FieldErrorCode::InvalidIcon,
+            format!("Icon {:?} is not a supported image type", path),
+        ));
+    }
+    if !path.is_file() {
+        return Some(error(
+            "icon",
+            FieldErrorCode::InvalidIcon,
+            format!("Icon file {:?} does not exist", path),
+        ));
+    }
+    None
+}
+
+pub fn validate_app_with_path(
+    app: &TauriApp,
+    existing: &HashMap<String, TauriApp>,
+    mode: ValidationMode,
+    path_var: &OsStr,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    if app.id.trim().is_empty() {
+        errors.push(error("id", FieldErrorCode::Empty, "Id is required"));
+    } else if !is_valid_id(&app.id) {
+        errors.push(error(
+            "id",
+            FieldErrorCode::InvalidId,
+            "Id may only contain letters, digits, '-' and '_'",
+        ));
+    } else {
+        match (mode, existing.contains_key(&app.id)) {
+            (ValidationMode::Register, true) => errors.push(error(
+                "id",
+                FieldErrorCode::IdCollision,
+                format!("An app with id '{}' is already registered", app.id),
+            )),
+            (ValidationMode::Update, false) => errors.push(error(
+                "id",
+                FieldErrorCode::NotFound,
+                format!("App with id '{}' not found", app.id),
+            )),
+            _ => {}
+        }
+    }
+
+    if app.name.trim().is_empty() {
+        errors.push(error("name", FieldErrorCode::Empty, "Name is required"));
+    }
+
+    let app_dir = expand_home(&app.path);
+    if app.path.as_os_str().is_empty() {
+        errors.push(error("path", FieldErrorCode::Empty, "Path is required"));
+    } else if !app_dir.exists() {
+        errors.push(error(
+            "path",
+            FieldErrorCode::PathNotFound,
+            format!("Path {:?} does not exist", app_dir),
+        ));
+    } else if !app_dir.is_dir() {
+        errors.push(error(
+            "path",
+            FieldErrorCode::NotADirectory,
+            format!("Path {:?} is not a directory", app_dir),
+        ));
+    }
+
+    // Executables are resolved relative to the working directory, so only check
+    // them once we know the directory is real
+    let path_ok = !errors.iter().any(|e| e.field == "path");
+
+    if app.profiles.is_empty() {
+        if path_ok {
+            errors.extend(check_executable("executable", &app.executable, &app_dir, path_var));
+        }
+    } else {
+        let mut names = HashSet::new();
+        for (i, profile) in app.profiles.iter().enumerate() {
+            if profile.name.trim().is_empty() {
+                errors.push(error(
+                    format!("profiles[{}].name", i),
+                    FieldErrorCode::Empty,
+                    "Profile name is required",
+                ));
+            } else if !names.insert(profile.name.as_str()) {
+                errors.push(error(
+                    format!("profiles[{}].name", i),
+                    FieldErrorCode::DuplicateProfile,
+                    format!("Profile '{}' is defined more than once", profile.name),
+                ));
+            }
+
+            let workdir = profiles::resolve_working_dir(&app.path, profile);
+            if path_ok && !workdir.is_dir() {
+                errors.push(error(
+                    format!("profiles[{}].working_dir", i),
+                    FieldErrorCode::PathNotFound,
+                    format!("Working directory {:?} does not exist", workdir),
+                ));
+            } else if path_ok {
+                let field = format!("profiles[{}].executable", i);
+                errors.extend(check_executable(&field, &profile.executable, &workdir, path_var));
+            }
+        }
+
+        if let Some(default) = &app.default_profile {
+            if !app.profiles.iter().any(|p| &p.name == default) {
+                errors.push(error(
+                    "default_profile",
+                    FieldErrorCode::UnknownProfile,
+                    format!("Default profile '{}' does not exist", default),
+                ));
+            }
+        }
+    }
+
+    if let Some(icon) = app.icon.as_deref().filter(|i| !i.trim().is_empty()) {
+        errors.extend(check_icon(icon, &app_dir));
+    }
+
+    errors
+}
+
+pub fn validate_app(
+    app: &TauriApp,
+    existing: &HashMap<String, TauriApp>,
+    mode: ValidationMode,
+) -> Vec<FieldError> {
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    validate_app_with_path(app, existing, mode, &path_var)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::LaunchProfile;
+    use crate::test_util::Fixture;
+    use std::ffi::OsString;
+
+    // pnpm on a PATH of its own, so the tests don't depend on what's installed
+    fn path_var(fx: &Fixture) -> OsString {
+        fx.write_with_mode("bin/pnpm", "", 0o755);
+        fx.dir.join("bin").into_os_string()
+    }
+
+    fn valid_app(fx: &Fixture) -> TauriApp {
+        serde_json::from_value(serde_json::json!({
+            "id": "app-1",
+            "name": "My App",
+            "description": "",
+            "path": fx.dir,
+            "executable": "pnpm",
+            "icon": "🚀",
+            "status": "Stopped",
+        }))
+        .unwrap()
+    }
+
+    fn validate(fx: &Fixture, app: &TauriApp, existing: &HashMap<String, TauriApp>, mode: ValidationMode) -> Vec<FieldError> {
+        validate_app_with_path(app, existing, mode, &path_var(fx))
+    }
+
+    fn codes(errors: &[FieldError]) -> Vec<(&str, FieldErrorCode)> {
+        errors.iter().map(|e| (e.field.as_str(), e.code)).collect()
+    }
+
+    #[test]
+    fn accepts_valid_app() {
+        let fx = Fixture::new("validation-valid");
+        let errors = validate(&fx, &valid_app(&fx), &HashMap::new(), ValidationMode::Register);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn rejects_empty_name() {
+        let fx = Fixture::new("validation-name");
+        let mut app = valid_app(&fx);
+        app.name = "  ".to_string();
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("name", FieldErrorCode::Empty)]);
+    }
+
+    #[test]
+    fn rejects_id_collision_on_register_only() {
+        let fx = Fixture::new("validation-collision");
+        let app = valid_app(&fx);
+        let existing = HashMap::from([(app.id.clone(), app.clone())]);
+
+        let errors = validate(&fx, &app, &existing, ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("id", FieldErrorCode::IdCollision)]);
+
+        let errors = validate(&fx, &app, &existing, ValidationMode::Update);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn rejects_ids_unsafe_in_paths_and_metric_names() {
+        let fx = Fixture::new("validation-bad-id");
+        let mut app = valid_app(&fx);
+        for id in ["../../x", "a.b", "a/b", "app 1"] {
+            app.id = id.to_string();
+            let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+            assert_eq!(codes(&errors), vec![("id", FieldErrorCode::InvalidId)], "{}", id);
+        }
+
+        app.id = "My_App-2".to_string();
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn rejects_update_of_unknown_app() {
+        let fx = Fixture::new("validation-unknown");
+        let errors = validate(&fx, &valid_app(&fx), &HashMap::new(), ValidationMode::Update);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "id");
+    }
+
+    #[test]
+    fn rejects_missing_path() {
+        let fx = Fixture::new("validation-path");
+        let mut app = valid_app(&fx);
+        app.path = fx.dir.join("does-not-exist");
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("path", FieldErrorCode::PathNotFound)]);
+    }
+
+    #[test]
+    fn rejects_path_that_is_a_file() {
+        let fx = Fixture::new("validation-file-path");
+        let mut app = valid_app(&fx);
+        app.path = fx.write("not-a-dir", "");
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("path", FieldErrorCode::NotADirectory)]);
+    }
+
+    #[test]
+    fn rejects_executable_missing_from_path() {
+        let fx = Fixture::new("validation-exe-missing");
+        let mut app = valid_app(&fx);
+        app.executable = "definitely-not-installed".to_string();
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("executable", FieldErrorCode::ExecutableNotFound)]);
+    }
+
+    #[test]
+    fn rejects_relative_executable_without_exec_bit() {
+        let fx = Fixture::new("validation-exe-mode");
+        fx.write_with_mode("run.sh", "#!/bin/sh\n", 0o644);
+        let mut app = valid_app(&fx);
+        app.executable = "./run.sh".to_string();
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("executable", FieldErrorCode::NotExecutable)]);
+
+        fx.write_with_mode("run.sh", "#!/bin/sh\n", 0o755);
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn checks_every_profile() {
+        let fx = Fixture::new("validation-profiles");
+        let mut app = valid_app(&fx);
+        let profile = |name: &str, executable: &str| LaunchProfile {
+            name: name.to_string(),
+            executable: executable.to_string(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            working_dir: None,
+        };
+        app.profiles = vec![
+            profile("dev", "pnpm"),
+            profile("dev", "./missing-binary"),
+        ];
+        app.default_profile = Some("release".to_string());
+
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(
+            codes(&errors),
+            vec![
+                ("profiles[1].name", FieldErrorCode::DuplicateProfile),
+                ("profiles[1].executable", FieldErrorCode::ExecutableNotFound),
+                ("default_profile", FieldErrorCode::UnknownProfile),
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_icons() {
+        let fx = Fixture::new("validation-icon");
+        let mut app = valid_app(&fx);
+
+        app.icon = Some("this is not an icon".to_string());
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("icon", FieldErrorCode::InvalidIcon)]);
+
+        app.icon = Some("icons/missing.png".to_string());
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert_eq!(codes(&errors), vec![("icon", FieldErrorCode::InvalidIcon)]);
+
+        fx.write("icon.png", "");
+        app.icon = Some("icon.png".to_string());
+        let errors = validate(&fx, &app, &HashMap::new(), ValidationMode::Register);
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::discovery::{self, DiscoveredApp};
+use crate::registry::save_registry;
+use crate::settings::SettingsState;
+use crate::supervisor::emit_app_status;
+use crate::{expand_home, AppRegistry, AppStatus, TauriApp};
+
+// Filesystem events usually come in bursts (git checkout, cp -r), wait for
+// things to settle before rescanning
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Full rescan even without events, for changes inotify can't see (e.g. on
+// network mounts or in directories deeper than we watch)
+const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+// Build output and dependencies churn constantly and never matter here
+const IGNORED_COMPONENTS: &[&str] = &["node_modules", "target", ".git", ".svelte-kit", "dist"];
+
+pub const REGISTRY_CHANGED_EVENT: &str = "registry-changed";
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryChangedEvent {
+    // Apps whose name, version or path was updated from disk
+    pub updated: Vec<String>,
+    pub missing: Vec<String>,
+    // Apps that were missing and whose path exists again
+    pub restored: Vec<String>,
+    // Projects that appeared under a discovery root and aren't registered yet
+    pub discovered: Vec<DiscoveredApp>,
+}
+
+impl RegistryChangedEvent {
+    fn is_empty(&self) -> bool {
+        self.updated.is_empty()
+            && self.missing.is_empty()
+            && self.restored.is_empty()
+            && self.discovered.is_empty()
+    }
+}
+
+pub enum WatchMsg {
+    Fs(notify::Result<notify::Event>),
+    Rescan,
+}
+
+// Lets commands ask the watcher to pick up new roots or apps right away
+pub struct AppWatcher(Mutex<Sender<WatchMsg>>);
+
+pub fn request_rescan(app_handle: &AppHandle) {
+    if let Some(watcher) = app_handle.try_state::<AppWatcher>() {
+        if let Ok(tx) = watcher.0.lock() {
+            let _ = tx.send(WatchMsg::Rescan);
+        }
+    }
+}
+
+#[derive(Default)]
+struct WatchState {
+    watched: HashSet<PathBuf>,
+    // productName/version last read from each app's tauri.conf.json
+    known: HashMap<String, (Option<String>, Option<String>)>,
+    // Unregistered projects already announced, so they're only pushed once
+    announced: HashSet<PathBuf>,
+}
+
+fn is_relevant(msg: &WatchMsg) -> bool {
+    match msg {
+        WatchMsg::Rescan => true,
+        WatchMsg::Fs(Ok(event)) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|path| {
+                    !path.components().any(|c| match c {
+                        Component::Normal(name) => {
+                            IGNORED_COMPONENTS.contains(&name.to_string_lossy().as_ref())
+                        }
+                        _ => false,
+                    })
+                })
+        }
+        WatchMsg::Fs(Err(e)) => {
+            eprintln!("App watch error: {:?}", e);
+            false
+        }
+    }
+}
+
+fn nearest_existing(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find(|p| p.is_dir()).map(|p| p.to_path_buf())
+}
+
+// Directories whose direct children we need to hear about: the roots and
+// their subdirectories (new projects), every project and its src-tauri
+// (config edits) and the parent of every registered app (deletes and moves)
+fn watch_set(roots: &[PathBuf], discovered: &[DiscoveredApp], apps: &HashMap<String, TauriApp>) -> HashSet<PathBuf> {
+    let mut set = HashSet::new();
+
+    for root in roots {
+        let root = expand_home(root);
+        if let Ok(entries) = fs::read_dir(&root) {
+            set.extend(
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+                    .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+                    .map(|e| e.path()),
+            );
+            set.insert(root);
+        }
+    }
+
+    let projects = discovered
+        .iter()
+        .map(|d| d.app.path.clone())
+        .chain(apps.values().map(|app| expand_home(&app.path)));
+    for project in projects {
+        if project.is_dir() {
+            set.insert(project.join("src-tauri"));
+            set.insert(project.clone());
+        }
+        if let Some(parent) = project.parent().and_then(nearest_existing) {
+            set.insert(parent);
+        }
+    }
+
+    set.retain(|path| path.is_dir());
+    set
+}
+
+fn sync_watches(watcher: &mut RecommendedWatcher, state: &mut WatchState, wanted: HashSet<PathBuf>) {
+    for path in state.watched.difference(&wanted) {
+        let _ = watcher.unwatch(path);
+    }
+    for path in wanted.difference(&state.watched) {
+        if let Err(e) = watcher.watch(path, RecursiveMode::NonRecursive) {
+            eprintln!("Failed to watch {:?}: {}", path, e);
+        }
+    }
+    state.watched = wanted;
+}
+
+// A registered app whose path vanished at the same moment an unregistered
+// project with the same name appeared was most likely moved or renamed
+fn find_moved(app: &TauriApp, discovered: &[DiscoveredApp], claimed: &HashSet<PathBuf>) -> Option<PathBuf> {
+    let mut candidates = discovered.iter().filter(|d| {
+        d.registered_as.is_none() && !claimed.contains(&d.app.path) && d.app.name == app.name
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(only), None) => Some(only.app.path.clone()),
+        _ => None,
+    }
+}
+
+// Update `apps` from what `discovered` says is on disk. Returns the event to
+// push to the frontend and the apps whose status needs re-emitting.
+fn sync_apps(
+    apps: &mut HashMap<String, TauriApp>,
+    discovered: &[DiscoveredApp],
+    state: &mut WatchState,
+) -> (RegistryChangedEvent, Vec<TauriApp>) {
+    let mut event = RegistryChangedEvent::default();
+    let mut changed = Vec::new();
+    let mut claimed = HashSet::new();
+
+    for app in apps.values_mut() {
+        let mut updated = false;
+
+        if !expand_home(&app.path).exists() {
+            if let Some(new_path) = find_moved(app, discovered, &claimed) {
+                eprintln!("App '{}' moved from {:?} to {:?}", app.name, app.path, new_path);
+                claimed.insert(new_path.clone());
+                app.path = new_path;
+                updated = true;
+            } else if !app.missing {
+                eprintln!("App '{}' is missing: {:?} no longer exists", app.name, app.path);
+                app.missing = true;
+                if !matches!(app.status, AppStatus::Running) {
+                    app.status_reason = Some(format!("{} no longer exists", app.path.display()));
+                }
+                event.missing.push(app.id.clone());
+                changed.push(app.clone());
+                continue;
+            } else {
+                continue;
+            }
+        }
+
+        let restored = app.missing;
+        if restored {
+            app.missing = false;
+            app.status_reason = None;
+            event.restored.push(app.id.clone());
+        }
+
+        let project = expand_home(&app.path);
+        if discovery::is_tauri_project(&project) {
+            match discovery::project_info(&project) {
+                Ok(info) => {
+                    // Follow productName renames, but not once the user picked
+                    // a name of their own
+                    if let Some((Some(old_name), _)) = state.known.get(&app.id) {
+                        if let Some(new_name) = info.name.as_ref().filter(|n| *n != old_name) {
+                            if app.name == *old_name {
+                                app.name = new_name.clone();
+                                updated = true;
+                            }
+                        }
+                    }
+                    if info.version.is_some() && app.version != info.version {
+                        app.version = info.version.clone();
+                        updated = true;
+                    }
+                    state.known.insert(app.id.clone(), (info.name, info.version));
+                }
+                Err(e) => eprintln!("Failed to read config of app '{}': {}", app.name, e),
+            }
+        }
+
+        if updated {
+            event.updated.push(app.id.clone());
+        }
+        if updated || restored {
+            changed.push(app.clone());
+        }
+    }
+
+    let unregistered: Vec<&DiscoveredApp> = discovered
+        .iter()
+        .filter(|d| d.registered_as.is_none() && !claimed.contains(&d.app.path))
+        .collect();
+    state
+        .announced
+        .retain(|path| unregistered.iter().any(|d| d.app.path == *path));
+    for found in unregistered {
+        if state.announced.insert(found.app.path.clone()) {
+            event.discovered.push(found.clone());
+        }
+    }
+
+    (event, changed)
+}
+
+// Bring the registry in line with what's on disk and return the watch set for
+// the next round
+fn reconcile(app_handle: &AppHandle, state: &mut WatchState) -> Result<HashSet<PathBuf>, String> {
+    let roots = app_handle
+        .state::<SettingsState>()
+        .lock()
+        .map_err(|e| e.to_string())?
+        .discovery_roots
+        .clone();
+
+    let registry = app_handle.state::<AppRegistry>();
+    let snapshot = registry.lock().map_err(|e| e.to_string())?.clone();
+
+    // Walking the roots can take a while, don't hold the registry for it
+    let discovered = discovery::discover(&roots, &snapshot);
+
+    let mut apps = registry.lock().map_err(|e| e.to_string())?;
+    let (event, changed) = sync_apps(&mut apps, &discovered, state);
+    if !changed.is_empty() {
+        if let Err(e) = save_registry(app_handle, &apps) {
+            eprintln!("Failed to save registry after filesystem change: {}", e);
+        }
+    }
+    let wanted = watch_set(&roots, &discovered, &apps);
+    drop(apps);
+
+    for app in &changed {
+        emit_app_status(app_handle, app);
+    }
+    if !event.is_empty() {
+        if let Err(e) = app_handle.emit(REGISTRY_CHANGED_EVENT, event) {
+            eprintln!("Failed to emit {} event: {}", REGISTRY_CHANGED_EVENT, e);
+        }
+    }
+
+    Ok(wanted)
+}
+
+fn run(app_handle: AppHandle, mut watcher: RecommendedWatcher, rx: Receiver<WatchMsg>) {
+    let mut state = WatchState::default();
+
+    loop {
+        match reconcile(&app_handle, &mut state) {
+            Ok(wanted) => sync_watches(&mut watcher, &mut state, wanted),
+            Err(e) => eprintln!("Failed to sync apps with the filesystem: {}", e),
+        }
+
+        // Sleep until something relevant happens or the periodic rescan is due.
+        // Both waits run against a fixed deadline, so a steady stream of events
+        // (a dev build writing target/) can't push the rescan back forever.
+        let rescan_at = Instant::now() + RESCAN_INTERVAL;
+        loop {
+            match rx.recv_timeout(rescan_at.saturating_duration_since(Instant::now())) {
+                Ok(msg) if is_relevant(&msg) => break,
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let settled_at = Instant::now() + DEBOUNCE;
+        loop {
+            match rx.recv_timeout(settled_at.saturating_duration_since(Instant::now())) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+// Watch the discovery roots and every registered app so the registry follows
+// projects being added, moved, deleted or renamed on disk
+pub fn start_app_watcher(app_handle: &AppHandle) -> Result<(), String> {
+    let (tx, rx) = channel();
+
+    let fs_tx = tx.clone();
+    let watcher = notify::recommended_watcher(move |res| {
+        let _ = fs_tx.send(WatchMsg::Fs(res));
+    })
+    .map_err(|e| format!("Failed to create app watcher: {}", e))?;
+
+    app_handle.manage(AppWatcher(Mutex::new(tx)));
+
+    let app_handle = app_handle.clone();
+    thread::spawn(move || run(app_handle, watcher, rx));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Fixture;
+
+    fn project(fx: &Fixture, dir: &str, name: &str, version: &str) {
+        fx.write(
+            &format!("{}/src-tauri/tauri.conf.json", dir),
+            &format!(r#"{{ "productName": "{}", "version": "{}" }}"#, name, version),
+        );
+        fx.write(&format!("{}/package.json", dir), "{}");
+    }
+
+    // One watcher round without the AppHandle: discover, then sync
+    fn sync(
+        fx: &Fixture,
+        apps: &mut HashMap<String, TauriApp>,
+        state: &mut WatchState,
+    ) -> RegistryChangedEvent {
+        let discovered = discovery::discover(&[fx.dir.join("apps")], apps);
+        sync_apps(apps, &discovered, state).0
+    }
+
+    // Register every project currently under the root
+    fn registered(fx: &Fixture) -> HashMap<String, TauriApp> {
+        discovery::discover(&[fx.dir.join("apps")], &HashMap::new())
+            .into_iter()
+            .map(|found| (found.app.id.clone(), found.app))
+            .collect()
+    }
+
+    #[test]
+    fn follows_config_changes() {
+        let fx = Fixture::new("watch-config");
+        project(&fx, "apps/notes", "Notes", "1.0.0");
+        let mut apps = registered(&fx);
+        let mut state = WatchState::default();
+
+        assert!(sync(&fx, &mut apps, &mut state).is_empty());
+
+        project(&fx, "apps/notes", "Notes Pro", "1.1.0");
+        let event = sync(&fx, &mut apps, &mut state);
+        assert_eq!(event.updated, vec!["notes"]);
+        assert_eq!(apps["notes"].name, "Notes Pro");
+        assert_eq!(apps["notes"].version.as_deref(), Some("1.1.0"));
+
+        // A name the user picked is left alone
+        apps.get_mut("notes").unwrap().name = "My Notes".to_string();
+        project(&fx, "apps/notes", "Notes Max", "1.1.0");
+        assert!(sync(&fx, &mut apps, &mut state).is_empty());
+        assert_eq!(apps["notes"].name, "My Notes");
+    }
+
+    #[test]
+    fn marks_deleted_apps_missing_until_they_return() {
+        let fx = Fixture::new("watch-missing");
+        project(&fx, "apps/notes", "Notes", "1.0.0");
+        let mut apps = registered(&fx);
+        let mut state = WatchState::default();
+        sync(&fx, &mut apps, &mut state);
+
+        fs::rename(fx.dir.join("apps/notes"), fx.dir.join("parked")).unwrap();
+        let (event, changed) = {
+            let discovered = discovery::discover(&[fx.dir.join("apps")], &apps);
+            sync_apps(&mut apps, &discovered, &mut state)
+        };
+        assert_eq!(event.missing, vec!["notes"]);
+        assert_eq!(changed.len(), 1);
+        assert!(apps["notes"].missing);
+        assert!(apps["notes"].status_reason.as_deref().unwrap().ends_with("no longer exists"));
+
+        // Only reported once
+        assert!(sync(&fx, &mut apps, &mut state).is_empty());
+
+        fs::rename(fx.dir.join("parked"), fx.dir.join("apps/notes")).unwrap();
+        let event = sync(&fx, &mut apps, &mut state);
+        assert_eq!(event.restored, vec!["notes"]);
+        assert!(!apps["notes"].missing);
+        assert_eq!(apps["notes"].status_reason, None);
+    }
+
+    #[test]
+    fn follows_moved_apps() {
+        let fx = Fixture::new("watch-moved");
+        project(&fx, "apps/notes", "Notes", "1.0.0");
+        let mut apps = registered(&fx);
+        let mut state = WatchState::default();
+        sync(&fx, &mut apps, &mut state);
+
+        fs::rename(fx.dir.join("apps/notes"), fx.dir.join("apps/notes-app")).unwrap();
+        let event = sync(&fx, &mut apps, &mut state);
+        assert_eq!(event.updated, vec!["notes"]);
+        assert!(event.missing.is_empty());
+        assert!(event.discovered.is_empty());
+        assert_eq!(apps["notes"].path, fx.dir.join("apps/notes-app"));
+    }
+
+    #[test]
+    fn announces_new_projects_once() {
+        let fx = Fixture::new("watch-new");
+        project(&fx, "apps/notes", "Notes", "1.0.0");
+        let mut apps = registered(&fx);
+        let mut state = WatchState::default();
+        sync(&fx, &mut apps, &mut state);
+
+        project(&fx, "apps/paint", "Paint", "0.1.0");
+        let event = sync(&fx, &mut apps, &mut state);
+        assert_eq!(event.discovered.len(), 1);
+        assert_eq!(event.discovered[0].app.name, "Paint");
+        assert!(!apps.contains_key("paint"));
+
+        assert!(sync(&fx, &mut apps, &mut state).is_empty());
+    }
+}